@@ -9,7 +9,7 @@ fn main() -> Result<(), AllenError> {
 
     let context = device.create_context()?;
     context.make_current();
-    context.set_distance_model(DistanceModel::Linear);
+    context.set_distance_model(DistanceModel::Linear)?;
 
     assert!(context.is_current());
 
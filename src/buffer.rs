@@ -1,40 +1,189 @@
 use crate::{
-    check_al_error, check_al_extension, getter, properties::PropertiesContainer, sys::*,
-    AllenResult, Context,
+    check_al_error, check_al_extension, get_proc_address, getter, getter_setter,
+    is_extension_present, properties::PropertiesContainer, setter, sys::*, AllenError, AllenResult,
+    ChannelLayout, Device,
 };
 use num_derive::{FromPrimitive, ToPrimitive};
-use num_traits::FromPrimitive;
+use num_traits::{FromPrimitive, ToPrimitive};
 use std::{
+    cell::Cell,
     ffi::{c_void, CString},
-    mem::size_of,
+    mem::{align_of, size_of, size_of_val},
+    ops::{Deref, DerefMut, Range},
+    slice,
+    time::Duration,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Channels {
     /// One audio channel.
     Mono,
     /// Two audio channels; one left & one right.
     Stereo,
+    /// Two audio channels; rear left & rear right. Requires extension ``AL_EXT_MCFORMATS``.
+    Rear,
+    /// Four audio channels; front left & right, rear left & right. Requires extension ``AL_EXT_MCFORMATS``.
+    Quad,
+    /// 5.1 surround; front left, right & center, LFE, side left & right. Requires extension ``AL_EXT_MCFORMATS``.
+    Surround51,
+    /// 6.1 surround; 5.1 along with a rear center channel. Requires extension ``AL_EXT_MCFORMATS``.
+    Surround61,
+    /// 7.1 surround; 5.1 along with rear left & right. Requires extension ``AL_EXT_MCFORMATS``.
+    Surround71,
+    /// Horizontal-only ambisonics (B-Format); W, X & Y at first order. Requires extension ``AL_EXT_BFORMAT``.
+    /// Higher orders need [`Buffer::set_unpack_ambisonic_order`].
+    ///
+    /// The sound field is laid out around the source's orientation (see [`crate::Source::set_orientation`]).
+    /// On a source that isn't relative, the field stays put in the world and turns along with the listener's orientation;
+    /// on a relative source, it follows the listener around instead.
+    BFormat2D,
+    /// Full-sphere ambisonics (B-Format); W, X, Y & Z at first order. See [`Channels::BFormat2D`].
+    BFormat3D,
+    /// Two-channel UHJ, decoded to surround. Plays as plain stereo where UHJ can't be decoded. Requires extension ``AL_SOFT_UHJ``.
+    Uhj2,
+    /// Three-channel UHJ. Requires extension ``AL_SOFT_UHJ``.
+    Uhj3,
+    /// Four-channel UHJ. Requires extension ``AL_SOFT_UHJ``.
+    Uhj4,
+}
+
+impl Channels {
+    /// The amount of channels in a sample frame; for ambisonics, at first order.
+    pub fn count(&self) -> i32 {
+        match self {
+            Channels::Mono => 1,
+            Channels::Stereo | Channels::Rear | Channels::Uhj2 => 2,
+            Channels::BFormat2D | Channels::Uhj3 => 3,
+            Channels::Quad | Channels::BFormat3D | Channels::Uhj4 => 4,
+            Channels::Surround51 => 6,
+            Channels::Surround61 => 7,
+            Channels::Surround71 => 8,
+        }
+    }
+
+    /// The speaker layout with the given amount of channels. Two channels are taken to be [`Channels::Stereo`],
+    /// as OpenAL doesn't tell it apart from [`Channels::Rear`].
+    pub fn from_count(count: i32) -> Option<Channels> {
+        match count {
+            1 => Some(Channels::Mono),
+            2 => Some(Channels::Stereo),
+            4 => Some(Channels::Quad),
+            6 => Some(Channels::Surround51),
+            7 => Some(Channels::Surround61),
+            8 => Some(Channels::Surround71),
+            _ => None,
+        }
+    }
+
+    /// The extension the layout needs, if any.
+    fn extension(&self) -> Option<&'static str> {
+        match self {
+            Channels::Mono | Channels::Stereo => None,
+            Channels::Rear
+            | Channels::Quad
+            | Channels::Surround51
+            | Channels::Surround61
+            | Channels::Surround71 => Some("AL_EXT_MCFORMATS"),
+            Channels::BFormat2D | Channels::BFormat3D => Some("AL_EXT_BFORMAT"),
+            Channels::Uhj2 | Channels::Uhj3 | Channels::Uhj4 => Some("AL_SOFT_UHJ"),
+        }
+    }
+}
+
+/// The order of the channels in ambisonic data. Requires extension ``AL_SOFT_bformat_ex``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AmbisonicLayout {
+    /// Furse-Malham ordering (W, X, Y, Z, ...). The default.
+    FuMa = AL_FUMA_SOFT as isize,
+    /// Ambisonic Channel Number ordering (W, Y, Z, X, ...).
+    Acn = AL_ACN_SOFT as isize,
+}
+
+/// The normalization of the channels in ambisonic data. Requires extension ``AL_SOFT_bformat_ex``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AmbisonicScaling {
+    /// Furse-Malham scaling. The default.
+    FuMa = AL_FUMA_SOFT as isize,
+    /// Schmidt semi-normalized.
+    Sn3d = AL_SN3D_SOFT as isize,
+    /// Fully normalized.
+    N3d = AL_N3D_SOFT as isize,
 }
 
 /// Container for OpenAL buffer data to be passed into [`Buffer::data`].
 #[derive(Debug, Clone)]
 pub enum BufferData<'a> {
-    /// AL_FORMAT_*8
-    I8(&'a [i8]),
+    /// AL_FORMAT_*8; unsigned, centered on 128.
+    U8(&'a [u8]),
     /// AL_FORMAT_*16
     I16(&'a [i16]),
     /// AL_FORMAT_*_FLOAT32; requires extension ``AL_EXT_float32``.
     F32(&'a [f32]),
-    /// AL_FORMAT_*_DOUBLE_EXT; requires extension ``AL_EXT_double``.
+    /// AL_FORMAT_*_DOUBLE_EXT; requires extension ``AL_EXT_double``. Only available in mono and stereo, unless converted (see [`Conversion`]).
     F64(&'a [f64]),
+    /// AL_FORMAT_*_MULAW; requires extension ``AL_EXT_MULAW``, and ``AL_EXT_MULAW_MCFORMATS`` or ``AL_EXT_MULAW_BFORMAT`` for more than two channels.
+    MuLaw(&'a [u8]),
+    /// AL_FORMAT_*_ALAW_EXT; requires extension ``AL_EXT_ALAW``. Only available in mono and stereo.
+    ALaw(&'a [u8]),
+    /// AL_FORMAT_*_IMA4, in blocks of 65 samples per channel unless set otherwise (see [`Buffer::set_unpack_block_alignment`]);
+    /// requires extension ``AL_EXT_IMA4``. Only available in mono and stereo.
+    Ima4(&'a [u8]),
+    /// AL_FORMAT_*_MSADPCM_SOFT, in blocks of 64 samples per channel unless set otherwise (see [`Buffer::set_unpack_block_alignment`]);
+    /// requires extension ``AL_SOFT_MSADPCM``. Only available in mono and stereo.
+    MsAdpcm(&'a [u8]),
+}
+
+/// The sample format of [`BufferData`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SampleFormat {
+    U8,
+    I16,
+    F32,
+    F64,
+    MuLaw,
+    ALaw,
+    Ima4,
+    MsAdpcm,
+}
+
+/// How [`Buffer::data_with_conversion`] deals with sample formats that aren't supported.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Conversion {
+    /// Fails with [`crate::AllenError::MissingExtension`].
+    Strict,
+    /// Converts f64 samples to f32, and f32 samples to i16, until the format is supported.
+    #[default]
+    Convert,
+    /// Like [`Conversion::Convert`], with triangular dither added when converting to i16.
+    ConvertWithDither,
 }
 
 impl BufferData<'_> {
+    pub fn sample_format(&self) -> SampleFormat {
+        match self {
+            BufferData::U8(_) => SampleFormat::U8,
+            BufferData::I16(_) => SampleFormat::I16,
+            BufferData::F32(_) => SampleFormat::F32,
+            BufferData::F64(_) => SampleFormat::F64,
+            BufferData::MuLaw(_) => SampleFormat::MuLaw,
+            BufferData::ALaw(_) => SampleFormat::ALaw,
+            BufferData::Ima4(_) => SampleFormat::Ima4,
+            BufferData::MsAdpcm(_) => SampleFormat::MsAdpcm,
+        }
+    }
+
     fn ptr(&self) -> *const c_void {
         match self {
-            BufferData::I8(data) => data.as_ptr() as *const c_void,
+            BufferData::U8(data)
+            | BufferData::MuLaw(data)
+            | BufferData::ALaw(data)
+            | BufferData::Ima4(data)
+            | BufferData::MsAdpcm(data) => data.as_ptr() as *const c_void,
             BufferData::I16(data) => data.as_ptr() as *const c_void,
             BufferData::F32(data) => data.as_ptr() as *const c_void,
             BufferData::F64(data) => data.as_ptr() as *const c_void,
@@ -43,25 +192,312 @@ impl BufferData<'_> {
 
     fn size(&self) -> usize {
         match self {
-            BufferData::I8(data) => size_of::<i8>() * data.len(),
+            BufferData::U8(data)
+            | BufferData::MuLaw(data)
+            | BufferData::ALaw(data)
+            | BufferData::Ima4(data)
+            | BufferData::MsAdpcm(data) => size_of::<u8>() * data.len(),
             BufferData::I16(data) => size_of::<i16>() * data.len(),
             BufferData::F32(data) => size_of::<f32>() * data.len(),
             BufferData::F64(data) => size_of::<f64>() * data.len(),
         }
     }
+
+    /// The AL format for the data in the given layout, after checking the extensions it needs.
+    /// Expects the context to be current.
+    fn format(&self, channels: Channels) -> AllenResult<i32> {
+        if let Some(extension) = channels.extension() {
+            check_al_extension(&CString::new(extension).unwrap())?;
+        }
+
+        let format = match self {
+            BufferData::U8(_) => match channels {
+                Channels::Mono => AL_FORMAT_MONO8,
+                Channels::Stereo => AL_FORMAT_STEREO8,
+                Channels::Rear => AL_FORMAT_REAR8,
+                Channels::Quad => AL_FORMAT_QUAD8,
+                Channels::Surround51 => AL_FORMAT_51CHN8,
+                Channels::Surround61 => AL_FORMAT_61CHN8,
+                Channels::Surround71 => AL_FORMAT_71CHN8,
+                Channels::BFormat2D => AL_FORMAT_BFORMAT2D_8,
+                Channels::BFormat3D => AL_FORMAT_BFORMAT3D_8,
+                Channels::Uhj2 => AL_FORMAT_UHJ2CHN8_SOFT,
+                Channels::Uhj3 => AL_FORMAT_UHJ3CHN8_SOFT,
+                Channels::Uhj4 => AL_FORMAT_UHJ4CHN8_SOFT,
+            },
+            BufferData::I16(_) => match channels {
+                Channels::Mono => AL_FORMAT_MONO16,
+                Channels::Stereo => AL_FORMAT_STEREO16,
+                Channels::Rear => AL_FORMAT_REAR16,
+                Channels::Quad => AL_FORMAT_QUAD16,
+                Channels::Surround51 => AL_FORMAT_51CHN16,
+                Channels::Surround61 => AL_FORMAT_61CHN16,
+                Channels::Surround71 => AL_FORMAT_71CHN16,
+                Channels::BFormat2D => AL_FORMAT_BFORMAT2D_16,
+                Channels::BFormat3D => AL_FORMAT_BFORMAT3D_16,
+                Channels::Uhj2 => AL_FORMAT_UHJ2CHN16_SOFT,
+                Channels::Uhj3 => AL_FORMAT_UHJ3CHN16_SOFT,
+                Channels::Uhj4 => AL_FORMAT_UHJ4CHN16_SOFT,
+            },
+            BufferData::F32(_) => match channels {
+                Channels::Mono => {
+                    check_al_extension(&CString::new("AL_EXT_float32").unwrap())?;
+                    AL_FORMAT_MONO_FLOAT32
+                }
+                Channels::Stereo => {
+                    check_al_extension(&CString::new("AL_EXT_float32").unwrap())?;
+                    AL_FORMAT_STEREO_FLOAT32
+                }
+                // The 32-bit multichannel formats are float formats.
+                Channels::Rear => AL_FORMAT_REAR32,
+                Channels::Quad => AL_FORMAT_QUAD32,
+                Channels::Surround51 => AL_FORMAT_51CHN32,
+                Channels::Surround61 => AL_FORMAT_61CHN32,
+                Channels::Surround71 => AL_FORMAT_71CHN32,
+                Channels::BFormat2D => AL_FORMAT_BFORMAT2D_FLOAT32,
+                Channels::BFormat3D => AL_FORMAT_BFORMAT3D_FLOAT32,
+                Channels::Uhj2 => AL_FORMAT_UHJ2CHN_FLOAT32_SOFT,
+                Channels::Uhj3 => AL_FORMAT_UHJ3CHN_FLOAT32_SOFT,
+                Channels::Uhj4 => AL_FORMAT_UHJ4CHN_FLOAT32_SOFT,
+            },
+            BufferData::F64(_) => {
+                check_al_extension(&CString::new("AL_EXT_double").unwrap())?;
+                match channels {
+                    Channels::Mono => AL_FORMAT_MONO_DOUBLE_EXT,
+                    Channels::Stereo => AL_FORMAT_STEREO_DOUBLE_EXT,
+                    _ => return Err(AllenError::InvalidEnum),
+                }
+            }
+            BufferData::MuLaw(_) => {
+                check_al_extension(&CString::new("AL_EXT_MULAW").unwrap())?;
+                let (format, extension) = match channels {
+                    Channels::Mono => (AL_FORMAT_MONO_MULAW_EXT, None),
+                    Channels::Stereo => (AL_FORMAT_STEREO_MULAW_EXT, None),
+                    Channels::Rear => (AL_FORMAT_REAR_MULAW, Some("AL_EXT_MULAW_MCFORMATS")),
+                    Channels::Quad => (AL_FORMAT_QUAD_MULAW, Some("AL_EXT_MULAW_MCFORMATS")),
+                    Channels::Surround51 => (AL_FORMAT_51CHN_MULAW, Some("AL_EXT_MULAW_MCFORMATS")),
+                    Channels::Surround61 => (AL_FORMAT_61CHN_MULAW, Some("AL_EXT_MULAW_MCFORMATS")),
+                    Channels::Surround71 => (AL_FORMAT_71CHN_MULAW, Some("AL_EXT_MULAW_MCFORMATS")),
+                    Channels::BFormat2D => {
+                        (AL_FORMAT_BFORMAT2D_MULAW, Some("AL_EXT_MULAW_BFORMAT"))
+                    }
+                    Channels::BFormat3D => {
+                        (AL_FORMAT_BFORMAT3D_MULAW, Some("AL_EXT_MULAW_BFORMAT"))
+                    }
+                    _ => return Err(AllenError::InvalidEnum),
+                };
+                if let Some(extension) = extension {
+                    check_al_extension(&CString::new(extension).unwrap())?;
+                }
+                format
+            }
+            BufferData::ALaw(_) => {
+                check_al_extension(&CString::new("AL_EXT_ALAW").unwrap())?;
+                match channels {
+                    Channels::Mono => AL_FORMAT_MONO_ALAW_EXT,
+                    Channels::Stereo => AL_FORMAT_STEREO_ALAW_EXT,
+                    _ => return Err(AllenError::InvalidEnum),
+                }
+            }
+            BufferData::Ima4(_) => {
+                check_al_extension(&CString::new("AL_EXT_IMA4").unwrap())?;
+                match channels {
+                    Channels::Mono => AL_FORMAT_MONO_IMA4,
+                    Channels::Stereo => AL_FORMAT_STEREO_IMA4,
+                    _ => return Err(AllenError::InvalidEnum),
+                }
+            }
+            BufferData::MsAdpcm(_) => {
+                check_al_extension(&CString::new("AL_SOFT_MSADPCM").unwrap())?;
+                match channels {
+                    Channels::Mono => AL_FORMAT_MONO_MSADPCM_SOFT,
+                    Channels::Stereo => AL_FORMAT_STEREO_MSADPCM_SOFT,
+                    _ => return Err(AllenError::InvalidEnum),
+                }
+            }
+        };
+
+        Ok(format)
+    }
+}
+
+/// Plain sample types, which buffers can be filled with (see [`Buffer::upload`]) and which buffer memory can be viewed as (see [`Buffer::map`]).
+pub trait Sample: Copy + sealed::Sealed + 'static {
+    /// The sample as a float from -1 to 1.
+    fn to_f64(self) -> f64;
+
+    /// The sample for a float from -1 to 1, clamping values out of range.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Sample for u8 {
+    fn to_f64(self) -> f64 {
+        (self as f64 - 128.0) / 128.0
+    }
+
+    fn from_f64(value: f64) -> Self {
+        (value * 128.0 + 128.0).round().clamp(0.0, u8::MAX as f64) as u8
+    }
+}
+
+impl Sample for i16 {
+    fn to_f64(self) -> f64 {
+        self as f64 / -(i16::MIN as f64)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        (value * -(i16::MIN as f64))
+            .round()
+            .clamp(i16::MIN as f64, i16::MAX as f64) as i16
+    }
+}
+
+impl Sample for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value.clamp(-1.0, 1.0) as f32
+    }
+}
+
+impl Sample for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value.clamp(-1.0, 1.0)
+    }
+}
+
+pub(crate) mod sealed {
+    use super::BufferData;
+
+    pub trait Sealed: Sized {
+        fn buffer_data(data: &[Self]) -> BufferData<'_>;
+    }
+
+    impl Sealed for u8 {
+        fn buffer_data(data: &[Self]) -> BufferData<'_> {
+            BufferData::U8(data)
+        }
+    }
+
+    impl Sealed for i16 {
+        fn buffer_data(data: &[Self]) -> BufferData<'_> {
+            BufferData::I16(data)
+        }
+    }
+
+    impl Sealed for f32 {
+        fn buffer_data(data: &[Self]) -> BufferData<'_> {
+            BufferData::F32(data)
+        }
+    }
+
+    impl Sealed for f64 {
+        fn buffer_data(data: &[Self]) -> BufferData<'_> {
+            BufferData::F64(data)
+        }
+    }
+}
+
+/// Flags for storage set up with [`Buffer::storage`], built up by chaining, e.g. `StorageFlags::new().write().persistent()`.
+/// The same flags are passed to [`Buffer::map`], where they have to be a subset of the ones the storage was set up with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StorageFlags(ALbitfieldSOFT);
+
+impl StorageFlags {
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    /// Allows the buffer to be mapped for reading.
+    pub fn read(self) -> Self {
+        Self(self.0 | AL_MAP_READ_BIT_SOFT as ALbitfieldSOFT)
+    }
+
+    /// Allows the buffer to be mapped for writing.
+    pub fn write(self) -> Self {
+        Self(self.0 | AL_MAP_WRITE_BIT_SOFT as ALbitfieldSOFT)
+    }
+
+    /// Allows the buffer to stay mapped while sources play it. Needs [`StorageFlags::read`] or [`StorageFlags::write`] as well.
+    pub fn persistent(self) -> Self {
+        Self(self.0 | AL_MAP_PERSISTENT_BIT_SOFT as ALbitfieldSOFT)
+    }
+
+    /// Keeps the existing data when storage is set up again in the same format and size. Only applies to [`Buffer::storage`].
+    pub fn preserve_data(self) -> Self {
+        Self(self.0 | AL_PRESERVE_DATA_BIT_SOFT as ALbitfieldSOFT)
+    }
 }
 
 /// Buffer of audio data.
-/// NOTE: Buffers are bound to a device.
+/// NOTE: Buffers are bound to a device, and can be attached to sources of any of its contexts (see [`Device::new_buffer`]).
 /// To ensure safety, buffers are not allowed to be cloned. There can only be one instance per-handle.
 pub struct Buffer {
     handle: u32,
-    context: Context,
+    device: Device,
+    /// The layout the buffer was last filled with, as AL_CHANNELS only gives a count.
+    channels: Cell<Option<Channels>>,
+    /// The blocks of the ADPCM data the buffer was last filled with, if any, as AL_BITS doesn't make up a whole sample for those.
+    block: Cell<Option<AdpcmBlock>>,
+}
+
+/// The layout of a block of ADPCM data, for each channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AdpcmBlock {
+    samples: i32,
+    bytes: i32,
+}
+
+impl AdpcmBlock {
+    /// The blocks of data in the given format, with the given amount of samples per block (0 for the format's default).
+    fn new(format: SampleFormat, alignment: i32) -> Option<Self> {
+        match format {
+            // A 4-byte header holding the first sample, followed by two samples per byte.
+            SampleFormat::Ima4 => {
+                let samples = if alignment > 0 { alignment } else { 65 };
+                Some(Self {
+                    samples,
+                    bytes: 4 + (samples - 1) / 2,
+                })
+            }
+            // A 7-byte header holding the first two samples, followed by two samples per byte.
+            SampleFormat::MsAdpcm => {
+                let samples = if alignment > 0 { alignment } else { 64 };
+                Some(Self {
+                    samples,
+                    bytes: 7 + (samples - 2) / 2,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The amount of sample frames in `size` bytes of data, made of samples of `bits` bits or of ADPCM blocks.
+/// `None` if the samples don't take whole bytes and the blocks are unknown.
+fn frame_count(size: i32, bits: i32, channels: i32, block: Option<AdpcmBlock>) -> Option<i32> {
+    let (frame_size, frame_samples) = match block {
+        Some(block) => (block.bytes * channels, block.samples),
+        None if bits % 8 == 0 => (bits / 8 * channels, 1),
+        None => return None,
+    };
+
+    Some(if frame_size > 0 {
+        size / frame_size * frame_samples
+    } else {
+        0
+    })
 }
 
 impl PropertiesContainer<f32> for Buffer {
     fn get(&self, param: i32) -> AllenResult<f32> {
-        let _lock = self.context.make_current();
+        let _lock = self.device.make_current();
 
         let result = unsafe {
             let mut value = 0.0;
@@ -75,7 +511,7 @@ impl PropertiesContainer<f32> for Buffer {
     }
 
     fn set(&self, param: i32, value: f32) -> AllenResult<()> {
-        let _lock = self.context.make_current();
+        let _lock = self.device.make_current();
 
         unsafe { alBufferf(self.handle, param, value) };
         check_al_error()?;
@@ -86,7 +522,7 @@ impl PropertiesContainer<f32> for Buffer {
 
 impl PropertiesContainer<[f32; 3]> for Buffer {
     fn get(&self, param: i32) -> AllenResult<[f32; 3]> {
-        let _lock = self.context.make_current();
+        let _lock = self.device.make_current();
 
         let result = unsafe {
             let mut value = [0.0, 0.0, 0.0];
@@ -106,7 +542,7 @@ impl PropertiesContainer<[f32; 3]> for Buffer {
     }
 
     fn set(&self, param: i32, value: [f32; 3]) -> AllenResult<()> {
-        let _lock = self.context.make_current();
+        let _lock = self.device.make_current();
 
         unsafe { alBuffer3f(self.handle, param, value[0], value[1], value[2]) };
         check_al_error()?;
@@ -117,7 +553,7 @@ impl PropertiesContainer<[f32; 3]> for Buffer {
 
 impl PropertiesContainer<i32> for Buffer {
     fn get(&self, param: i32) -> AllenResult<i32> {
-        let _lock = self.context.make_current();
+        let _lock = self.device.make_current();
 
         let result = unsafe {
             let mut value = 0;
@@ -131,7 +567,7 @@ impl PropertiesContainer<i32> for Buffer {
     }
 
     fn set(&self, param: i32, value: i32) -> AllenResult<()> {
-        let _lock = self.context.make_current();
+        let _lock = self.device.make_current();
 
         unsafe { alBufferi(self.handle, param, value) };
         check_al_error()?;
@@ -140,23 +576,32 @@ impl PropertiesContainer<i32> for Buffer {
     }
 }
 
-impl PropertiesContainer<Channels> for Buffer {
-    fn get(&self, param: i32) -> AllenResult<Channels> {
+impl PropertiesContainer<AmbisonicLayout> for Buffer {
+    fn get(&self, param: i32) -> AllenResult<AmbisonicLayout> {
         Ok(FromPrimitive::from_i32(PropertiesContainer::<i32>::get(self, param)?).unwrap())
     }
 
-    fn set(&self, _param: i32, _value: Channels) -> AllenResult<()> {
-        // AL_CHANNELS does not support writing.
-        unimplemented!()
+    fn set(&self, param: i32, value: AmbisonicLayout) -> AllenResult<()> {
+        PropertiesContainer::<i32>::set(self, param, ToPrimitive::to_i32(&value).unwrap())
+    }
+}
+
+impl PropertiesContainer<AmbisonicScaling> for Buffer {
+    fn get(&self, param: i32) -> AllenResult<AmbisonicScaling> {
+        Ok(FromPrimitive::from_i32(PropertiesContainer::<i32>::get(self, param)?).unwrap())
+    }
+
+    fn set(&self, param: i32, value: AmbisonicScaling) -> AllenResult<()> {
+        PropertiesContainer::<i32>::set(self, param, ToPrimitive::to_i32(&value).unwrap())
     }
 }
 
 impl Buffer {
-    pub(crate) fn new(context: Context) -> AllenResult<Self> {
+    pub(crate) fn new(device: Device) -> AllenResult<Self> {
         let handle = {
             let mut handle = 0;
             unsafe {
-                let _lock = context.make_current();
+                let _lock = device.make_current();
                 alGenBuffers(1, &mut handle)
             };
 
@@ -165,65 +610,320 @@ impl Buffer {
             handle
         };
 
-        Ok(Self { handle, context })
+        Ok(Self {
+            handle,
+            device,
+            channels: Cell::new(None),
+            block: Cell::new(None),
+        })
     }
 
     pub(crate) fn handle(&self) -> u32 {
         self.handle
     }
 
-    /// Fills the buffer with data.
-    pub fn data(&self, data: BufferData, channels: Channels, sample_rate: i32) -> AllenResult<()> {
-        let _lock = self.context.make_current();
+    /// The device the buffer belongs to.
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
 
-        let format = match data {
-            BufferData::I8(_) => match channels {
-                Channels::Mono => AL_FORMAT_MONO8,
-                Channels::Stereo => AL_FORMAT_MONO16,
-            },
-            BufferData::I16(_) => match channels {
-                Channels::Mono => AL_FORMAT_MONO16,
-                Channels::Stereo => AL_FORMAT_STEREO16,
-            },
-            BufferData::F32(_) => {
-                check_al_extension(&CString::new("AL_EXT_float32").unwrap())?;
-                match channels {
-                    Channels::Mono => AL_FORMAT_MONO_FLOAT32,
-                    Channels::Stereo => AL_FORMAT_STEREO_FLOAT32,
+    /// Fills the buffer with data, converting it if its sample format isn't supported (see [`Conversion::Convert`]).
+    /// Returns the sample format the data was uploaded in.
+    /// For ambisonics, the layout, scaling and order have to be set beforehand (see [`Buffer::set_ambisonic_layout`]).
+    pub fn data(
+        &self,
+        data: BufferData,
+        channels: Channels,
+        sample_rate: i32,
+    ) -> AllenResult<SampleFormat> {
+        self.data_with_conversion(data, channels, sample_rate, Conversion::default())
+    }
+
+    /// Fills the buffer with data like [`Buffer::data`], choosing how unsupported sample formats are dealt with.
+    pub fn data_with_conversion(
+        &self,
+        data: BufferData,
+        channels: Channels,
+        sample_rate: i32,
+        conversion: Conversion,
+    ) -> AllenResult<SampleFormat> {
+        let (f32_data, i16_data);
+        let mut data = data;
+
+        if conversion != Conversion::Strict {
+            let target = {
+                let _lock = self.device.make_current();
+                converted_format(data.sample_format(), channels, |name| {
+                    is_extension_present(&CString::new(name).unwrap())
+                })?
+            };
+
+            if let BufferData::F64(samples) = data {
+                if target != SampleFormat::F64 {
+                    f32_data = samples
+                        .iter()
+                        .map(|&sample| sample as f32)
+                        .collect::<Vec<_>>();
+                    data = BufferData::F32(&f32_data);
                 }
             }
-            BufferData::F64(_) => {
-                check_al_extension(&CString::new("AL_double").unwrap())?;
-                match channels {
-                    Channels::Mono => AL_FORMAT_MONO_DOUBLE_EXT,
-                    Channels::Stereo => AL_FORMAT_STEREO_DOUBLE_EXT,
+
+            if let BufferData::F32(samples) = data {
+                if target == SampleFormat::I16 {
+                    i16_data = to_i16(samples, conversion == Conversion::ConvertWithDither);
+                    data = BufferData::I16(&i16_data);
                 }
             }
+        }
+
+        let format = data.sample_format();
+        self.fill(data, channels, sample_rate, None)?;
+
+        Ok(format)
+    }
+
+    /// Fills the buffer with sample frames whose layout is known at compile time, e.g. `[i16; 2]` for [`crate::layout::Stereo`].
+    /// Otherwise the same as [`Buffer::data`]: whether the sample type and layout are supported together is only known at runtime,
+    /// so e.g. f64 frames of multichannel layouts are converted to f32, and layouts missing their extension fail.
+    pub fn upload<T: Sample, C: ChannelLayout>(
+        &self,
+        frames: &[C::Frame<T>],
+        sample_rate: i32,
+    ) -> AllenResult<SampleFormat> {
+        // Frames are arrays of samples, so they can be viewed as one flat slice.
+        let samples = unsafe {
+            slice::from_raw_parts(
+                frames.as_ptr() as *const T,
+                size_of_val(frames) / size_of::<T>(),
+            )
         };
 
+        self.data(T::buffer_data(samples), C::CHANNELS, sample_rate)
+    }
+
+    /// Fills the buffer with data like [`Buffer::data`], setting up storage that can be mapped with [`Buffer::map`].
+    /// Requires extension ``AL_SOFT_map_buffer``.
+    pub fn storage(
+        &self,
+        data: BufferData,
+        channels: Channels,
+        sample_rate: i32,
+        flags: StorageFlags,
+    ) -> AllenResult<()> {
+        self.fill(data, channels, sample_rate, Some(flags))
+    }
+
+    fn fill(
+        &self,
+        data: BufferData,
+        channels: Channels,
+        sample_rate: i32,
+        flags: Option<StorageFlags>,
+    ) -> AllenResult<()> {
+        let block = self.upload_block(data.sample_format())?;
+
+        let _lock = self.device.make_current();
+
+        let format = data.format(channels)?;
+
+        match flags {
+            Some(flags) => {
+                check_al_extension(&CString::new("AL_SOFT_map_buffer").unwrap())?;
+
+                let function: LPALBUFFERSTORAGESOFT = get_proc_address("alBufferStorageSOFT");
+                let function = function.expect("AL_SOFT_map_buffer is present");
+
+                unsafe {
+                    function(
+                        self.handle,
+                        format,
+                        data.ptr(),
+                        data.size() as i32,
+                        sample_rate,
+                        flags.0,
+                    )
+                };
+            }
+            None => unsafe {
+                alBufferData(
+                    self.handle,
+                    format,
+                    data.ptr(),
+                    data.size() as i32,
+                    sample_rate,
+                )
+            },
+        }
+
+        check_al_error()?;
+        self.channels.set(Some(channels));
+        self.block.set(block);
+
+        Ok(())
+    }
+
+    /// The blocks data in the given format is uploaded in, if it's ADPCM.
+    fn upload_block(&self, format: SampleFormat) -> AllenResult<Option<AdpcmBlock>> {
+        if !matches!(format, SampleFormat::Ima4 | SampleFormat::MsAdpcm) {
+            return Ok(None);
+        }
+
+        let has_alignment = {
+            let _lock = self.device.make_current();
+            is_extension_present(&CString::new("AL_SOFT_block_alignment").unwrap())?
+        };
+
+        let alignment = if has_alignment {
+            PropertiesContainer::<i32>::get(self, AL_UNPACK_BLOCK_ALIGNMENT_SOFT)?
+        } else {
+            0
+        };
+
+        Ok(AdpcmBlock::new(format, alignment))
+    }
+
+    /// Points the buffer at the data instead of copying it. Requires extension ``AL_EXT_STATIC_BUFFER``.
+    ///
+    /// # Safety
+    /// The data has to stay alive and in place until the buffer is deleted or refilled, and no source may be playing it by then.
+    pub(crate) unsafe fn data_static(
+        &self,
+        data: BufferData,
+        channels: Channels,
+        sample_rate: i32,
+    ) -> AllenResult<()> {
+        let block = self.upload_block(data.sample_format())?;
+
+        let _lock = self.device.make_current();
+        check_al_extension(&CString::new("AL_EXT_STATIC_BUFFER").unwrap())?;
+
+        let format = data.format(channels)?;
+
+        let function: PFNALBUFFERDATASTATICPROC = get_proc_address("alBufferDataStatic");
+        let function = function.expect("AL_EXT_STATIC_BUFFER is present");
+
+        function(
+            self.handle as i32,
+            format,
+            data.ptr() as *mut c_void,
+            data.size() as i32,
+            sample_rate,
+        );
+
+        check_al_error()?;
+        self.channels.set(Some(channels));
+        self.block.set(block);
+
+        Ok(())
+    }
+
+    /// Replaces part of the buffer's data in place, starting `offset` bytes in. Requires extension ``AL_SOFT_buffer_sub_data``.
+    /// The data has to be in the format the buffer was filled with (as returned by [`Buffer::data`]), and has to line up with whole sample frames
+    /// (or blocks, for compressed formats).
+    pub fn sub_data(&self, offset: usize, data: BufferData) -> AllenResult<()> {
+        let channels = self.channels()?;
+
+        let _lock = self.device.make_current();
+        check_al_extension(&CString::new("AL_SOFT_buffer_sub_data").unwrap())?;
+
+        let format = data.format(channels)?;
+
+        let function: LPALBUFFERSUBDATASOFT = get_proc_address("alBufferSubDataSOFT");
+        let function = function.expect("AL_SOFT_buffer_sub_data is present");
+
         unsafe {
-            alBufferData(
+            function(
                 self.handle,
                 format,
                 data.ptr(),
+                offset as i32,
                 data.size() as i32,
-                sample_rate,
             )
         };
 
         check_al_error()
     }
 
+    /// Maps the whole buffer. See [`Buffer::map_range`].
+    pub fn map<T: Sample>(&mut self, flags: StorageFlags) -> AllenResult<MappedBuffer<'_, T>> {
+        let len = self.size()? as usize / size_of::<T>();
+        self.map_range(0, len, flags)
+    }
+
+    /// Maps `len` samples of the buffer, starting `offset` samples in, so they can be read or written in place.
+    /// Requires extension ``AL_SOFT_map_buffer``.
+    ///
+    /// The buffer has to be set up with [`Buffer::storage`], and the flags have to be a subset of the storage's.
+    /// Unless the mapping is persistent, sources can't play the buffer while it's mapped.
+    pub fn map_range<T: Sample>(
+        &mut self,
+        offset: usize,
+        len: usize,
+        flags: StorageFlags,
+    ) -> AllenResult<MappedBuffer<'_, T>> {
+        let _lock = self.device.make_current();
+        check_al_extension(&CString::new("AL_SOFT_map_buffer").unwrap())?;
+
+        let function: LPALMAPBUFFERSOFT = get_proc_address("alMapBufferSOFT");
+        let function = function.expect("AL_SOFT_map_buffer is present");
+
+        let (offset, len_in_bytes) = (offset * size_of::<T>(), len * size_of::<T>());
+
+        let ptr = unsafe { function(self.handle, offset as i32, len_in_bytes as i32, flags.0) };
+        check_al_error()?;
+
+        if ptr.is_null() {
+            return Err(AllenError::InvalidOperation);
+        }
+        assert_eq!(ptr as usize % align_of::<T>(), 0);
+
+        Ok(MappedBuffer {
+            buffer: self,
+            offset,
+            data: unsafe { slice::from_raw_parts_mut(ptr as *mut T, len) },
+        })
+    }
+
     getter!(frequency, i32, AL_FREQUENCY);
     getter!(size, i32, AL_SIZE);
     getter!(bits, i32, AL_BITS);
-    getter!(channels, Channels, AL_CHANNELS);
+
+    /// The channel layout of the buffer. For buffers filled through [`Buffer::data`], this is the layout that was passed in;
+    /// otherwise it's guessed from the amount of channels (see [`Channels::from_count`]).
+    pub fn channels(&self) -> AllenResult<Channels> {
+        if let Some(channels) = self.channels.get() {
+            return Ok(channels);
+        }
+
+        Channels::from_count(self.get(AL_CHANNELS)?).ok_or(AllenError::InvalidEnum)
+    }
+
+    /// The amount of sample frames in the buffer.
+    /// Fails with [`AllenError::InvalidOperation`] for ADPCM data that wasn't uploaded through this buffer, as its block size is unknown.
+    pub fn length_in_samples(&self) -> AllenResult<i32> {
+        // AL_CHANNELS is read directly, as the count is all that matters here.
+        let channels = PropertiesContainer::<i32>::get(self, AL_CHANNELS)?;
+
+        frame_count(self.size()?, self.bits()?, channels, self.block.get())
+            .ok_or(AllenError::InvalidOperation)
+    }
+
+    /// How long the buffer takes to play at its own sample rate.
+    pub fn duration(&self) -> AllenResult<Duration> {
+        let frequency = self.frequency()?;
+
+        Ok(if frequency > 0 {
+            Duration::from_secs_f64(self.length_in_samples()? as f64 / frequency as f64)
+        } else {
+            Duration::ZERO
+        })
+    }
 
     // AL_SOFT_loop_points
     pub fn loop_points(&self) -> AllenResult<[i32; 2]> {
         check_al_extension(&CString::new("AL_SOFT_loop_points").unwrap())?;
 
-        let _lock = self.context.make_current();
+        let _lock = self.device.make_current();
 
         let result = unsafe {
             let mut value = [0i32; 2];
@@ -239,20 +939,346 @@ impl Buffer {
     pub fn set_loop_points(&self, value: &[i32; 2]) -> AllenResult<()> {
         check_al_extension(&CString::new("AL_SOFT_loop_points").unwrap())?;
 
-        let _lock = self.context.make_current();
+        let _lock = self.device.make_current();
 
         unsafe { alBufferiv(self.handle, AL_LOOP_POINTS_SOFT, value.as_ptr()) };
         check_al_error()?;
 
         Ok(())
     }
+
+    /// The loop points as a range of sample frames. Requires extension ``AL_SOFT_loop_points``.
+    pub fn loop_range(&self) -> AllenResult<Range<usize>> {
+        let [start, end] = self.loop_points()?;
+        Ok(start as usize..end as usize)
+    }
+
+    /// Sets the loop points to a range of sample frames. Requires extension ``AL_SOFT_loop_points``.
+    /// Fails with [`AllenError::InvalidValue`] if the range is empty or goes past the end of the buffer (see [`Buffer::length_in_samples`]).
+    ///
+    /// NOTE: The loop points can't be changed while the buffer is attached to a source.
+    pub fn set_loop_range(&self, range: Range<usize>) -> AllenResult<()> {
+        check_loop_range(&range, self.length_in_samples()? as usize)?;
+        self.set_loop_points(&[range.start as i32, range.end as i32])
+    }
+
+    /// Sets the loop points to a span of time, rounded to the nearest sample frame. See [`Buffer::set_loop_range`].
+    pub fn set_loop_times(&self, range: Range<Duration>) -> AllenResult<()> {
+        let start = self.duration_to_frames(range.start)?;
+        let end = self.duration_to_frames(range.end)?;
+
+        self.set_loop_range(start..end)
+    }
+
+    fn duration_to_frames(&self, duration: Duration) -> AllenResult<usize> {
+        Ok((duration.as_secs_f64() * self.frequency()? as f64).round() as usize)
+    }
+}
+
+#[rustfmt::skip]
+impl Buffer {
+    // AL_SOFT_bformat_ex; these apply to the data passed to Buffer::data afterwards.
+    getter_setter!(ambisonic_layout, set_ambisonic_layout, AmbisonicLayout, AL_AMBISONIC_LAYOUT_SOFT, "AL_SOFT_bformat_ex");
+    getter_setter!(ambisonic_scaling, set_ambisonic_scaling, AmbisonicScaling, AL_AMBISONIC_SCALING_SOFT, "AL_SOFT_bformat_ex");
+
+    // AL_SOFT_bformat_hoa; the ambisonic order of the data passed to Buffer::data afterwards, from 1 up to 3 or more.
+    getter_setter!(unpack_ambisonic_order, set_unpack_ambisonic_order, i32, AL_UNPACK_AMBISONIC_ORDER_SOFT, "AL_SOFT_bformat_hoa");
+
+    // AL_SOFT_block_alignment; the samples per block of compressed data passed to Buffer::data afterwards, and of data read back. 0 is the format's default.
+    getter_setter!(unpack_block_alignment, set_unpack_block_alignment, i32, AL_UNPACK_BLOCK_ALIGNMENT_SOFT, "AL_SOFT_block_alignment");
+    getter_setter!(pack_block_alignment, set_pack_block_alignment, i32, AL_PACK_BLOCK_ALIGNMENT_SOFT, "AL_SOFT_block_alignment");
+}
+
+/// Whether a range of sample frames can be used as loop points of a buffer with the given length.
+fn check_loop_range(range: &Range<usize>, length: usize) -> AllenResult<()> {
+    if range.is_empty() || range.end > length {
+        return Err(AllenError::InvalidValue);
+    }
+
+    Ok(())
+}
+
+/// The format [`Buffer::data_with_conversion`] converts samples to, given whether an extension is present.
+fn converted_format(
+    format: SampleFormat,
+    channels: Channels,
+    mut is_extension_present: impl FnMut(&str) -> AllenResult<bool>,
+) -> AllenResult<SampleFormat> {
+    let is_stereo_or_mono = matches!(channels, Channels::Mono | Channels::Stereo);
+    let mut format = format;
+
+    // Doubles only come in mono and stereo.
+    if format == SampleFormat::F64
+        && (!is_stereo_or_mono || !is_extension_present("AL_EXT_double")?)
+    {
+        format = SampleFormat::F32;
+    }
+
+    // Multichannel floats come with the extensions of their layout.
+    if format == SampleFormat::F32 && is_stereo_or_mono && !is_extension_present("AL_EXT_float32")?
+    {
+        format = SampleFormat::I16;
+    }
+
+    Ok(format)
+}
+
+/// Converts float samples to 16-bit, optionally with triangular (TPDF) dither.
+fn to_i16(samples: &[f32], dither: bool) -> Vec<i16> {
+    // A small xorshift generator is plenty for dither noise.
+    let mut state = 0x9E37_79B9u32;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32
+    };
+
+    samples
+        .iter()
+        .map(|&sample| {
+            // Up to a step of the output either way.
+            let noise = if dither { random() - random() } else { 0.0 };
+            let step = -(i16::MIN as f64);
+            <i16 as Sample>::from_f64((sample as f64).clamp(-1.0, 1.0) + noise as f64 / step)
+        })
+        .collect()
+}
+
+/// Mapped buffer memory, created with [`Buffer::map`]. The buffer is unmapped when this is dropped.
+pub struct MappedBuffer<'a, T: Sample> {
+    buffer: &'a Buffer,
+    /// In bytes.
+    offset: usize,
+    data: &'a mut [T],
+}
+
+impl<T: Sample> MappedBuffer<'_, T> {
+    /// Makes writes through a persistent mapping visible to the sources playing the buffer. Requires extension ``AL_SOFT_map_buffer``.
+    pub fn flush(&self) -> AllenResult<()> {
+        let _lock = self.buffer.device.make_current();
+
+        let function: LPALFLUSHMAPPEDBUFFERSOFT = get_proc_address("alFlushMappedBufferSOFT");
+        let function = function.expect("AL_SOFT_map_buffer is present");
+
+        unsafe {
+            function(
+                self.buffer.handle,
+                self.offset as i32,
+                size_of_val(self.data) as i32,
+            )
+        };
+        check_al_error()
+    }
+}
+
+impl<T: Sample> Deref for MappedBuffer<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.data
+    }
+}
+
+impl<T: Sample> DerefMut for MappedBuffer<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.data
+    }
+}
+
+impl<T: Sample> Drop for MappedBuffer<'_, T> {
+    fn drop(&mut self) {
+        let _lock = self.buffer.device.make_current();
+
+        let function: LPALUNMAPBUFFERSOFT = get_proc_address("alUnmapBufferSOFT");
+        let function = function.expect("AL_SOFT_map_buffer is present");
+
+        unsafe { function(self.buffer.handle) };
+        if let Err(err) = check_al_error() {
+            println!("WARNING: Buffer unmap failed! {}", err);
+        }
+    }
 }
 
 impl Drop for Buffer {
     fn drop(&mut self) {
+        let _lock = self.device.make_current();
+
         unsafe { alDeleteBuffers(1, &self.handle) }
         if let Err(err) = check_al_error() {
             println!("WARNING: Buffer drop failed! {}", err);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_frames_of_pcm_data() {
+        assert_eq!(frame_count(400, 16, 2, None), Some(100));
+        assert_eq!(frame_count(400, 8, 1, None), Some(400));
+        assert_eq!(frame_count(400, 32, 1, None), Some(100));
+        assert_eq!(frame_count(0, 0, 0, None), Some(0));
+    }
+
+    #[test]
+    fn counts_frames_of_adpcm_data() {
+        let ima4 = AdpcmBlock::new(SampleFormat::Ima4, 0);
+        assert_eq!(
+            ima4,
+            Some(AdpcmBlock {
+                samples: 65,
+                bytes: 36
+            })
+        );
+        // Two blocks of stereo data.
+        assert_eq!(frame_count(36 * 2 * 2, 4, 2, ima4), Some(130));
+
+        let ms_adpcm = AdpcmBlock::new(SampleFormat::MsAdpcm, 0);
+        assert_eq!(
+            ms_adpcm,
+            Some(AdpcmBlock {
+                samples: 64,
+                bytes: 38
+            })
+        );
+        assert_eq!(frame_count(38 * 3, 4, 1, ms_adpcm), Some(192));
+
+        // The usual 256-byte mono blocks of WAV files.
+        let ms_adpcm = AdpcmBlock::new(SampleFormat::MsAdpcm, 500);
+        assert_eq!(
+            ms_adpcm,
+            Some(AdpcmBlock {
+                samples: 500,
+                bytes: 256
+            })
+        );
+        assert_eq!(frame_count(256 * 4, 4, 1, ms_adpcm), Some(2000));
+    }
+
+    #[test]
+    fn adpcm_data_needs_its_blocks() {
+        assert_eq!(frame_count(36 * 2, 4, 1, None), None);
+        assert_eq!(AdpcmBlock::new(SampleFormat::I16, 0), None);
+    }
+
+    #[test]
+    fn converts_to_i16_with_clipping() {
+        assert_eq!(
+            to_i16(&[-2.0, -1.0, 0.0, 0.5, 1.0, 2.0], false),
+            [i16::MIN, i16::MIN, 0, 16384, i16::MAX, i16::MAX]
+        );
+
+        // The same as converting the samples one by one.
+        let samples = [-0.7, -0.3, 0.1, 0.123, 0.9];
+        let converted: Vec<i16> = samples.iter().map(|&sample| convert(sample)).collect();
+        assert_eq!(to_i16(&samples, false), converted);
+    }
+
+    #[test]
+    fn dithers_within_one_step() {
+        let samples = [-1.0, -0.25, 0.0, 0.25, 1.0].repeat(200);
+        let plain = to_i16(&samples, false);
+        let dithered = to_i16(&samples, true);
+
+        assert_ne!(plain, dithered);
+        for (plain, dithered) in plain.iter().zip(&dithered) {
+            assert!((*plain as i32 - *dithered as i32).abs() <= 1);
+        }
+    }
+
+    fn convert<T: Sample, U: Sample>(sample: T) -> U {
+        U::from_f64(sample.to_f64())
+    }
+
+    #[test]
+    fn converts_samples_through_f64() {
+        for sample in [0u8, 1, 128, 200, 255] {
+            assert_eq!(convert::<_, u8>(convert::<_, f64>(sample)), sample);
+            assert_eq!(convert::<_, u8>(convert::<_, f32>(sample)), sample);
+        }
+        assert_eq!(convert::<_, i16>(128u8), 0);
+        assert_eq!(convert::<_, i16>(192u8), 16384);
+
+        assert_eq!(convert::<_, f64>(i16::MIN), -1.0);
+        assert_eq!(convert::<_, i16>(1.0f32), i16::MAX);
+        assert_eq!(convert::<_, i16>(-2.0f64), i16::MIN);
+        assert_eq!(convert::<_, u8>(1.0f32), u8::MAX);
+        assert_eq!(convert::<_, u8>(-1.0f64), 0);
+
+        assert_eq!(convert::<_, f32>(0.25f64), 0.25);
+        assert_eq!(convert::<_, f32>(1.5f64), 1.0);
+        assert_eq!(convert::<_, f64>(-1.5f64), -1.0);
+    }
+
+    #[test]
+    fn converts_formats_missing_extensions() {
+        let all = |_: &str| Ok(true);
+        let none = |_: &str| Ok(false);
+        let only = |extension: &'static str| move |name: &str| Ok(name == extension);
+
+        assert_eq!(
+            converted_format(SampleFormat::F64, Channels::Stereo, all).unwrap(),
+            SampleFormat::F64
+        );
+        assert_eq!(
+            converted_format(SampleFormat::F64, Channels::Mono, only("AL_EXT_float32")).unwrap(),
+            SampleFormat::F32
+        );
+        assert_eq!(
+            converted_format(SampleFormat::F64, Channels::Mono, none).unwrap(),
+            SampleFormat::I16
+        );
+        // AL_EXT_double only has mono and stereo formats.
+        assert_eq!(
+            converted_format(SampleFormat::F64, Channels::Quad, all).unwrap(),
+            SampleFormat::F32
+        );
+        assert_eq!(
+            converted_format(SampleFormat::F32, Channels::Quad, none).unwrap(),
+            SampleFormat::F32
+        );
+        assert_eq!(
+            converted_format(SampleFormat::F32, Channels::Stereo, only("AL_EXT_double")).unwrap(),
+            SampleFormat::I16
+        );
+
+        for format in [
+            SampleFormat::U8,
+            SampleFormat::I16,
+            SampleFormat::MuLaw,
+            SampleFormat::Ima4,
+        ] {
+            assert_eq!(
+                converted_format(format, Channels::Mono, none).unwrap(),
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn conversion_fails_with_the_extension_check() {
+        let result = converted_format(SampleFormat::F64, Channels::Mono, |_| {
+            Err(AllenError::InvalidContext)
+        });
+        assert!(matches!(result, Err(AllenError::InvalidContext)));
+    }
+
+    #[test]
+    fn checks_loop_ranges() {
+        assert!(check_loop_range(&(0..10), 10).is_ok());
+        assert!(check_loop_range(&(5..10), 10).is_ok());
+        assert!(check_loop_range(&(9..10), 10).is_ok());
+
+        assert!(check_loop_range(&(5..5), 10).is_err());
+        assert!(check_loop_range(&(0..11), 10).is_err());
+        assert!(check_loop_range(&(10..11), 10).is_err());
+        assert!(check_loop_range(&(0..0), 0).is_err());
+
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 6..5;
+        assert!(check_loop_range(&reversed, 10).is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use crate::{check_al_error, sys::*, AllenError, AllenResult, Context};
+use std::collections::HashMap;
+
+/// Identifies a [`Bus`] within its context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BusId(pub(crate) usize);
+
+struct BusNode {
+    name: String,
+    parent: Option<BusId>,
+    gain: f32,
+    /// Set by [`crate::Snapshot`]s, on top of the bus's own gain.
+    snapshot_gain: f32,
+    /// Set by [`crate::DuckingRule`]s.
+    duck_gain: f32,
+    is_muted: bool,
+    is_solo: bool,
+}
+
+struct Member {
+    bus: BusId,
+    /// The source's own gain, before the bus gain is applied.
+    gain: f32,
+}
+
+/// The buses of a context, along with the sources assigned to them.
+pub(crate) struct BusTree {
+    buses: Vec<BusNode>,
+    members: HashMap<u32, Member>,
+}
+
+impl Default for BusTree {
+    fn default() -> Self {
+        Self {
+            buses: vec![BusNode {
+                name: "master".to_string(),
+                parent: None,
+                gain: 1.0,
+                snapshot_gain: 1.0,
+                duck_gain: 1.0,
+                is_muted: false,
+                is_solo: false,
+            }],
+            members: HashMap::new(),
+        }
+    }
+}
+
+impl BusTree {
+    const MASTER: BusId = BusId(0);
+
+    fn ancestors(&self, id: BusId) -> impl Iterator<Item = BusId> + '_ {
+        std::iter::successors(Some(id), |id| self.buses[id.0].parent)
+    }
+
+    fn is_ancestor(&self, ancestor: BusId, id: BusId) -> bool {
+        self.ancestors(id).any(|id| id == ancestor)
+    }
+
+    /// The gain of a bus after its ancestors, mutes and solos are taken into account.
+    fn effective_gain(&self, id: BusId) -> f32 {
+        let solos = self
+            .buses
+            .iter()
+            .enumerate()
+            .filter(|(_, bus)| bus.is_solo)
+            .map(|(index, _)| BusId(index))
+            .collect::<Vec<_>>();
+
+        // A soloed bus silences every bus that's neither inside it nor on its way to the master bus.
+        let is_silenced_by_solo = !solos.is_empty()
+            && !solos
+                .iter()
+                .any(|&solo| self.is_ancestor(solo, id) || self.is_ancestor(id, solo));
+
+        if is_silenced_by_solo {
+            return 0.0;
+        }
+
+        self.ancestors(id)
+            .map(|id| &self.buses[id.0])
+            .map(|bus| {
+                if bus.is_muted {
+                    0.0
+                } else {
+                    bus.gain * bus.snapshot_gain * bus.duck_gain
+                }
+            })
+            .product()
+    }
+
+    pub(crate) fn find(&self, name: &str) -> Option<BusId> {
+        self.buses
+            .iter()
+            .position(|bus| bus.name == name)
+            .map(BusId)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.buses.len()
+    }
+
+    pub(crate) fn set_snapshot_gain(&mut self, id: BusId, value: f32) {
+        self.buses[id.0].snapshot_gain = value;
+    }
+
+    pub(crate) fn set_duck_gain(&mut self, id: BusId, value: f32) {
+        self.buses[id.0].duck_gain = value;
+    }
+
+    /// The sources assigned to a bus, or to any bus inside it.
+    pub(crate) fn members_within(&self, id: BusId) -> impl Iterator<Item = u32> + '_ {
+        self.members
+            .iter()
+            .filter(move |(_, member)| self.is_ancestor(id, member.bus))
+            .map(|(handle, _)| *handle)
+    }
+
+    /// Pushes the effective gain of every member source to AL. Expects the context to be current.
+    pub(crate) fn apply(&self) -> AllenResult<()> {
+        for (handle, member) in &self.members {
+            unsafe {
+                alSourcef(
+                    *handle,
+                    AL_GAIN,
+                    member.gain * self.effective_gain(member.bus),
+                )
+            };
+        }
+        check_al_error()
+    }
+
+    pub(crate) fn remove_member(&mut self, handle: u32) {
+        self.members.remove(&handle);
+    }
+}
+
+/// A mixer bus. Buses form a tree under the context's master bus (see [`Context::master_bus`]),
+/// and the gain of a source assigned to a bus is scaled by the gain of that bus and of all its ancestors.
+#[derive(Clone)]
+pub struct Bus {
+    context: Context,
+    id: BusId,
+}
+
+impl Bus {
+    pub fn id(&self) -> BusId {
+        self.id
+    }
+
+    pub fn name(&self) -> String {
+        self.context.inner.buses.borrow().buses[self.id.0]
+            .name
+            .clone()
+    }
+
+    /// The parent of the bus; `None` for the master bus.
+    pub fn parent(&self) -> Option<Bus> {
+        let parent = self.context.inner.buses.borrow().buses[self.id.0].parent;
+        parent.map(|id| self.context.bus_at(id))
+    }
+
+    /// Creates a bus under this one. Bus names have to be unique within a context.
+    pub fn new_child(&self, name: &str) -> AllenResult<Bus> {
+        let mut tree = self.context.inner.buses.borrow_mut();
+
+        if tree.buses.iter().any(|bus| bus.name == name) {
+            return Err(AllenError::InvalidName);
+        }
+
+        tree.buses.push(BusNode {
+            name: name.to_string(),
+            parent: Some(self.id),
+            gain: 1.0,
+            snapshot_gain: 1.0,
+            duck_gain: 1.0,
+            is_muted: false,
+            is_solo: false,
+        });
+
+        Ok(self.context.bus_at(BusId(tree.buses.len() - 1)))
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.context.inner.buses.borrow().buses[self.id.0].gain
+    }
+
+    pub fn set_gain(&self, value: f32) -> AllenResult<()> {
+        if !(value >= 0.0 && value.is_finite()) {
+            return Err(AllenError::InvalidValue);
+        }
+
+        self.update(|bus| bus.gain = value)
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.context.inner.buses.borrow().buses[self.id.0].is_muted
+    }
+
+    pub fn set_muted(&self, value: bool) -> AllenResult<()> {
+        self.update(|bus| bus.is_muted = value)
+    }
+
+    pub fn is_solo(&self) -> bool {
+        self.context.inner.buses.borrow().buses[self.id.0].is_solo
+    }
+
+    /// While any bus is soloed, only soloed buses (along with the buses inside them and the ones leading up to the master bus) can be heard.
+    pub fn set_solo(&self, value: bool) -> AllenResult<()> {
+        self.update(|bus| bus.is_solo = value)
+    }
+
+    /// The gain sources on this bus are scaled by, after taking every ancestor, mute and solo into account.
+    pub fn effective_gain(&self) -> f32 {
+        self.context.inner.buses.borrow().effective_gain(self.id)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut BusNode)) -> AllenResult<()> {
+        let _lock = self.context.make_current();
+        let mut tree = self.context.inner.buses.borrow_mut();
+
+        f(&mut tree.buses[self.id.0]);
+        tree.apply()
+    }
+}
+
+impl Context {
+    /// The root of the bus tree. Every other bus is created under it, directly or indirectly.
+    pub fn master_bus(&self) -> Bus {
+        self.bus_at(BusTree::MASTER)
+    }
+
+    /// Finds a bus by its name.
+    pub fn bus(&self, name: &str) -> Option<Bus> {
+        let id = self.inner.buses.borrow().find(name)?;
+        Some(self.bus_at(id))
+    }
+
+    /// Finds a bus by its id. Returns `None` if there's no such bus in this context.
+    ///
+    /// NOTE: Ids aren't tied to a context; an id taken from another context may stand for an unrelated bus here.
+    pub fn bus_by_id(&self, id: BusId) -> Option<Bus> {
+        (id.0 < self.inner.buses.borrow().buses.len()).then(|| self.bus_at(id))
+    }
+
+    /// Expects the id to belong to this context.
+    pub(crate) fn bus_at(&self, id: BusId) -> Bus {
+        Bus {
+            context: self.clone(),
+            id,
+        }
+    }
+
+    /// Assigns a source to a bus, or removes it from its bus. Expects the context to be current.
+    pub(crate) fn set_source_bus(&self, handle: u32, bus: Option<&Bus>) -> AllenResult<()> {
+        let mut tree = self.inner.buses.borrow_mut();
+
+        let own_gain = match tree.members.get(&handle) {
+            Some(member) => member.gain,
+            None => {
+                let mut value = 0.0;
+                unsafe { alGetSourcef(handle, AL_GAIN, &mut value) };
+                check_al_error()?;
+                value
+            }
+        };
+
+        let gain = match bus {
+            Some(bus) => {
+                if !std::sync::Arc::ptr_eq(&bus.context.inner, &self.inner) {
+                    return Err(AllenError::InvalidContext);
+                }
+
+                tree.members.insert(
+                    handle,
+                    Member {
+                        bus: bus.id,
+                        gain: own_gain,
+                    },
+                );
+                own_gain * tree.effective_gain(bus.id)
+            }
+            None => {
+                tree.members.remove(&handle);
+                own_gain
+            }
+        };
+
+        unsafe { alSourcef(handle, AL_GAIN, gain) };
+        check_al_error()
+    }
+
+    pub(crate) fn source_bus(&self, handle: u32) -> Option<Bus> {
+        let bus = self.inner.buses.borrow().members.get(&handle)?.bus;
+        Some(self.bus_at(bus))
+    }
+
+    /// The gain a source was given, before its bus is applied.
+    pub(crate) fn source_gain(&self, handle: u32) -> Option<f32> {
+        Some(self.inner.buses.borrow().members.get(&handle)?.gain)
+    }
+
+    /// Sets the gain of a source that's on a bus, applying the bus gain. Returns false if the source isn't on a bus.
+    /// Expects the context to be current.
+    pub(crate) fn set_source_gain(&self, handle: u32, value: f32) -> AllenResult<bool> {
+        if value < 0.0 {
+            return Err(AllenError::InvalidValue);
+        }
+
+        let mut tree = self.inner.buses.borrow_mut();
+
+        let Some(member) = tree.members.get_mut(&handle) else {
+            return Ok(false);
+        };
+        member.gain = value;
+
+        let bus = member.bus;
+        unsafe { alSourcef(handle, AL_GAIN, value * tree.effective_gain(bus)) };
+        check_al_error()?;
+
+        Ok(true)
+    }
+}
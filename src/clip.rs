@@ -0,0 +1,459 @@
+use crate::{
+    check_al_extension, AllenError, AllenResult, Buffer, Channels, Device, Sample, SampleFormat,
+};
+use std::{ffi::CString, ops::Range, time::Duration};
+
+/// A named position in an [`AudioClip`], e.g. where a footstep lands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CueMarker {
+    pub name: String,
+    /// In sample frames.
+    pub frame: usize,
+}
+
+/// The direction of a zero crossing. See [`AudioClip::find_zero_crossing`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Crossing {
+    /// From below zero to zero or above.
+    Rising,
+    /// From zero or above to below zero.
+    Falling,
+}
+
+/// Decoded audio kept on the CPU side, along with what's needed to turn it into a [`Buffer`] again,
+/// e.g. after the device is lost.
+/// Samples are interleaved; positions, lengths and ranges are in sample frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioClip<T: Sample> {
+    samples: Vec<T>,
+    channels: Channels,
+    sample_rate: i32,
+    loop_points: Option<Range<usize>>,
+    /// Sorted by frame.
+    cues: Vec<CueMarker>,
+}
+
+impl<T: Sample> AudioClip<T> {
+    /// Fails with [`AllenError::InvalidValue`] if the sample rate isn't positive,
+    /// or if the samples don't make up whole frames (see [`Channels::count`]).
+    pub fn new(samples: Vec<T>, channels: Channels, sample_rate: i32) -> AllenResult<Self> {
+        if sample_rate <= 0 || samples.len() % channels.count() as usize != 0 {
+            return Err(AllenError::InvalidValue);
+        }
+
+        Ok(Self {
+            samples,
+            channels,
+            sample_rate,
+            loop_points: None,
+            cues: vec![],
+        })
+    }
+
+    pub fn samples(&self) -> &[T] {
+        &self.samples
+    }
+
+    pub fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channels.count() as usize
+    }
+
+    /// The samples of one frame, one per channel.
+    pub fn frame(&self, index: usize) -> Option<&[T]> {
+        let count = self.channels.count() as usize;
+        self.samples.get(index * count..(index + 1) * count)
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames_to_duration(self.frame_count())
+    }
+
+    pub fn frames_to_duration(&self, frames: usize) -> Duration {
+        Duration::from_secs_f64(frames as f64 / self.sample_rate as f64)
+    }
+
+    /// The frame at a point in time, rounded down.
+    pub fn duration_to_frames(&self, duration: Duration) -> usize {
+        (duration.as_secs_f64() * self.sample_rate as f64) as usize
+    }
+
+    pub fn loop_points(&self) -> Option<Range<usize>> {
+        self.loop_points.clone()
+    }
+
+    /// Sets the range that loops. Fails with [`AllenError::InvalidValue`] if it's empty or goes past the end of the clip.
+    pub fn set_loop_points(&mut self, value: Option<Range<usize>>) -> AllenResult<()> {
+        if let Some(range) = &value {
+            if range.is_empty() || range.end > self.frame_count() {
+                return Err(AllenError::InvalidValue);
+            }
+        }
+
+        self.loop_points = value;
+        Ok(())
+    }
+
+    /// Finds the zero crossing nearest to a frame, at most `radius` frames away, going in the given direction if there is one.
+    /// A crossing is the first frame at or above zero after one below it (rising), or the other way around (falling).
+    /// Channels are mixed together to find crossings.
+    pub fn find_zero_crossing(
+        &self,
+        frame: usize,
+        radius: usize,
+        direction: Option<Crossing>,
+    ) -> Option<usize> {
+        let first = frame.saturating_sub(radius).max(1);
+        let last = frame
+            .saturating_add(radius)
+            .min(self.frame_count().saturating_sub(1));
+
+        (first..=last)
+            .filter(|&index| {
+                self.crossing(index).is_some_and(|crossing| {
+                    direction.map_or(true, |direction| crossing == direction)
+                })
+            })
+            .min_by_key(|&index| index.abs_diff(frame))
+    }
+
+    /// Moves a loop range onto zero crossings going the same way, each at most `radius` frames away,
+    /// so that the loop wraps around without a click. Returns `None` if there are no such crossings.
+    pub fn align_loop_points(&self, range: Range<usize>, radius: usize) -> Option<Range<usize>> {
+        let start = self.find_zero_crossing(range.start, radius, None)?;
+        let direction = self.crossing(start);
+
+        // The frame before the end then leads into the start the same way the frame before the start does.
+        let end = self.find_zero_crossing(range.end, radius, direction)?;
+
+        Some(start..end).filter(|range| !range.is_empty())
+    }
+
+    /// Whether a frame is a zero crossing, and which way it goes.
+    fn crossing(&self, index: usize) -> Option<Crossing> {
+        let mix = |index: usize| -> f64 {
+            self.frame(index).map_or(0.0, |frame| {
+                frame.iter().map(|sample| sample.to_f64()).sum()
+            })
+        };
+
+        let (before, after) = (mix(index.checked_sub(1)?), mix(index));
+
+        if before < 0.0 && after >= 0.0 {
+            Some(Crossing::Rising)
+        } else if before >= 0.0 && after < 0.0 {
+            Some(Crossing::Falling)
+        } else {
+            None
+        }
+    }
+
+    pub fn cues(&self) -> &[CueMarker] {
+        &self.cues
+    }
+
+    /// Finds a cue by its name.
+    pub fn cue(&self, name: &str) -> Option<&CueMarker> {
+        self.cues.iter().find(|cue| cue.name == name)
+    }
+
+    /// Adds a cue, replacing any cue with the same name. Fails with [`AllenError::InvalidValue`] if it's past the end of the clip.
+    pub fn add_cue(&mut self, name: &str, frame: usize) -> AllenResult<()> {
+        if frame > self.frame_count() {
+            return Err(AllenError::InvalidValue);
+        }
+
+        self.remove_cue(name);
+
+        let index = self.cues.partition_point(|cue| cue.frame <= frame);
+        self.cues.insert(
+            index,
+            CueMarker {
+                name: name.to_string(),
+                frame,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn remove_cue(&mut self, name: &str) {
+        self.cues.retain(|cue| cue.name != name);
+    }
+
+    /// Copies a range of frames into a new clip. Cues inside the range are kept, as are the loop points,
+    /// cut down to the range if they still overlap it.
+    /// Fails with [`AllenError::InvalidValue`] if the range goes past the end of the clip.
+    pub fn slice(&self, range: Range<usize>) -> AllenResult<AudioClip<T>> {
+        if range.start > range.end || range.end > self.frame_count() {
+            return Err(AllenError::InvalidValue);
+        }
+
+        let count = self.channels.count() as usize;
+
+        let shift = |frame: usize| frame - range.start;
+
+        let loop_points = self
+            .loop_points
+            .as_ref()
+            .map(|points| points.start.max(range.start)..points.end.min(range.end))
+            .filter(|points| !points.is_empty())
+            .map(|points| shift(points.start)..shift(points.end));
+
+        let cues = self
+            .cues
+            .iter()
+            .filter(|cue| range.contains(&cue.frame) || cue.frame == range.end)
+            .map(|cue| CueMarker {
+                name: cue.name.clone(),
+                frame: shift(cue.frame),
+            })
+            .collect();
+
+        Ok(AudioClip {
+            samples: self.samples[range.start * count..range.end * count].to_vec(),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            loop_points,
+            cues,
+        })
+    }
+
+    /// Converts the samples to another sample type. See [`Sample::from_f64`].
+    pub fn convert<U: Sample>(&self) -> AudioClip<U> {
+        AudioClip {
+            samples: self
+                .samples
+                .iter()
+                .map(|&sample| U::from_f64(sample.to_f64()))
+                .collect(),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            loop_points: self.loop_points.clone(),
+            cues: self.cues.clone(),
+        }
+    }
+
+    /// Creates a buffer holding the clip. See [`AudioClip::refresh`].
+    pub fn to_buffer(&self, device: &Device) -> AllenResult<Buffer> {
+        let buffer = device.new_buffer()?;
+        self.refresh(&buffer)?;
+        Ok(buffer)
+    }
+
+    /// Fills a buffer with the clip, like [`Buffer::data`], and applies the clip's loop points if it has any
+    /// (which requires extension ``AL_SOFT_loop_points``).
+    /// Returns the sample format the data was uploaded in.
+    pub fn refresh(&self, buffer: &Buffer) -> AllenResult<SampleFormat> {
+        if self.loop_points.is_some() {
+            let _lock = buffer.device().make_current();
+            check_al_extension(&CString::new("AL_SOFT_loop_points").unwrap())?;
+        }
+
+        let format = buffer.data(
+            T::buffer_data(&self.samples),
+            self.channels,
+            self.sample_rate,
+        )?;
+
+        if let Some(points) = &self.loop_points {
+            buffer.set_loop_points(&[points.start as i32, points.end as i32])?;
+        }
+
+        Ok(format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(frames: usize) -> AudioClip<i16> {
+        let samples = (0..frames as i16 * 2).collect();
+        AudioClip::new(samples, Channels::Stereo, 100).unwrap()
+    }
+
+    #[test]
+    fn needs_whole_frames() {
+        assert!(AudioClip::new(vec![0i16; 3], Channels::Stereo, 100).is_err());
+        assert!(AudioClip::new(vec![0i16; 4], Channels::Stereo, 0).is_err());
+        assert!(AudioClip::new(Vec::<i16>::new(), Channels::Stereo, 100).is_ok());
+
+        let clip = clip(50);
+        assert_eq!(clip.frame_count(), 50);
+        assert_eq!(clip.frame(1), Some(&[2, 3][..]));
+        assert_eq!(clip.frame(50), None);
+        assert_eq!(clip.duration(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn slices_within_bounds() {
+        let clip = clip(10);
+
+        let slice = clip.slice(2..5).unwrap();
+        assert_eq!(slice.samples(), [4, 5, 6, 7, 8, 9]);
+        assert_eq!(slice.frame_count(), 3);
+
+        assert_eq!(clip.slice(10..10).unwrap().frame_count(), 0);
+        assert_eq!(clip.slice(0..10).unwrap(), clip);
+        assert!(clip.slice(5..11).is_err());
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 5..2;
+        assert!(clip.slice(reversed).is_err());
+    }
+
+    #[test]
+    fn shifts_cues_and_loop_points_when_slicing() {
+        let mut clip = clip(10);
+        clip.add_cue("start", 0).unwrap();
+        clip.add_cue("inside", 4).unwrap();
+        clip.add_cue("end", 6).unwrap();
+        clip.add_cue("after", 8).unwrap();
+        clip.set_loop_points(Some(1..5)).unwrap();
+
+        let slice = clip.slice(3..6).unwrap();
+        assert_eq!(
+            slice.cues(),
+            [
+                CueMarker {
+                    name: "inside".to_string(),
+                    frame: 1
+                },
+                CueMarker {
+                    name: "end".to_string(),
+                    frame: 3
+                },
+            ]
+        );
+        assert_eq!(slice.loop_points(), Some(0..2));
+
+        assert_eq!(clip.slice(6..10).unwrap().loop_points(), None);
+    }
+
+    #[test]
+    fn keeps_cues_sorted() {
+        let mut clip = clip(10);
+        clip.add_cue("b", 5).unwrap();
+        clip.add_cue("a", 2).unwrap();
+        clip.add_cue("b", 1).unwrap();
+        assert!(clip.add_cue("c", 11).is_err());
+
+        let frames: Vec<_> = clip.cues().iter().map(|cue| cue.frame).collect();
+        assert_eq!(frames, [1, 2]);
+        assert_eq!(clip.cue("b").map(|cue| cue.frame), Some(1));
+    }
+
+    #[test]
+    fn converts_back_and_forth() {
+        let samples = vec![i16::MIN, -16384, 0, 16384, i16::MAX, 1];
+        let mut clip = AudioClip::new(samples, Channels::Mono, 100).unwrap();
+        clip.add_cue("cue", 3).unwrap();
+        clip.set_loop_points(Some(1..4)).unwrap();
+
+        let floats = clip.convert::<f32>();
+        assert_eq!(floats.samples()[0], -1.0);
+        assert_eq!(floats.cues(), clip.cues());
+        assert_eq!(floats.loop_points(), clip.loop_points());
+
+        assert_eq!(floats.convert::<i16>(), clip);
+
+        assert_eq!(clip.convert::<f64>().convert::<f32>(), floats);
+    }
+
+    /// 100 frames going up to zero or above at every multiple of 10, and below zero five frames later.
+    fn square(channels: Channels, offset: f32) -> AudioClip<f32> {
+        let samples = (0..100)
+            .map(|frame| if frame % 10 < 5 { 1.0 } else { -1.0 })
+            .flat_map(|sample| match channels {
+                Channels::Mono => vec![sample],
+                _ => vec![sample, offset],
+            })
+            .collect();
+
+        AudioClip::new(samples, channels, 100).unwrap()
+    }
+
+    #[test]
+    fn finds_nearest_zero_crossings() {
+        let clip = square(Channels::Mono, 0.0);
+
+        assert_eq!(clip.find_zero_crossing(12, 3, None), Some(10));
+        assert_eq!(
+            clip.find_zero_crossing(12, 3, Some(Crossing::Rising)),
+            Some(10)
+        );
+        assert_eq!(
+            clip.find_zero_crossing(12, 3, Some(Crossing::Falling)),
+            Some(15)
+        );
+        assert_eq!(
+            clip.find_zero_crossing(13, 2, Some(Crossing::Falling)),
+            Some(15)
+        );
+    }
+
+    #[test]
+    fn finds_no_zero_crossing_outside_the_window() {
+        let clip = square(Channels::Mono, 0.0);
+
+        assert_eq!(clip.find_zero_crossing(12, 1, None), None);
+        assert_eq!(
+            clip.find_zero_crossing(12, 2, Some(Crossing::Falling)),
+            None
+        );
+        // The first frame has nothing before it to cross from.
+        assert_eq!(clip.find_zero_crossing(0, 0, None), None);
+        assert_eq!(clip.find_zero_crossing(0, 4, None), None);
+    }
+
+    #[test]
+    fn finds_zero_crossings_at_the_end() {
+        let clip = square(Channels::Mono, 0.0);
+
+        assert_eq!(
+            clip.find_zero_crossing(100, 10, Some(Crossing::Rising)),
+            Some(90)
+        );
+        assert_eq!(clip.find_zero_crossing(100, 5, None), Some(95));
+        assert_eq!(clip.find_zero_crossing(100, 4, None), None);
+        assert_eq!(clip.find_zero_crossing(200, 50, None), None);
+        assert_eq!(
+            clip.find_zero_crossing(usize::MAX, usize::MAX, None),
+            Some(95)
+        );
+    }
+
+    #[test]
+    fn mixes_channels_to_find_zero_crossings() {
+        // The other channel keeps the mix above zero.
+        let clip = square(Channels::Stereo, 2.0);
+        assert_eq!(clip.find_zero_crossing(50, 50, None), None);
+
+        // Here it only moves the mix, which still crosses zero in the same places.
+        let clip = square(Channels::Stereo, 0.5);
+        assert_eq!(clip.find_zero_crossing(12, 3, None), Some(10));
+        assert_eq!(clip.align_loop_points(8..92, 3), Some(10..90));
+    }
+
+    #[test]
+    fn aligns_loop_points_to_crossings_going_the_same_way() {
+        let clip = square(Channels::Mono, 0.0);
+
+        assert_eq!(clip.align_loop_points(8..92, 3), Some(10..90));
+        assert_eq!(clip.align_loop_points(14..97, 2), Some(15..95));
+        // The end is near a falling crossing only, while the start rises.
+        assert_eq!(clip.align_loop_points(9..96, 2), None);
+        // Both ends land on the same crossing.
+        assert_eq!(clip.align_loop_points(9..12, 3), None);
+        assert_eq!(clip.align_loop_points(0..100, 2), None);
+    }
+}
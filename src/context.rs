@@ -1,11 +1,17 @@
-use crate::{get_string, sys::*, AllenResult, Buffer, Device, Listener, Source};
+use crate::{
+    check_al_error, check_al_extension, get_proc_address, get_string, is_extension_present, sys::*,
+    AllenError, AllenResult, Buffer, BusTree, Device, Ducking, EventCallback, Listener,
+    OneShotPool, Parameters, Snapshots, Source, SourceGenerations, Tweens, VoiceManager,
+};
 use lazy_static::lazy_static;
 use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
 use std::{
-    cell::RefCell,
-    ffi::CString,
+    cell::{Cell, RefCell},
+    ffi::{CStr, CString},
     ptr,
     sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
 };
 
 lazy_static! {
@@ -15,6 +21,8 @@ lazy_static! {
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DistanceModel {
+    /// No attenuation over distance.
+    None = AL_NONE as isize,
     Inverse = AL_INVERSE_DISTANCE as isize,
     #[default]
     InverseClamped = AL_INVERSE_DISTANCE_CLAMPED as isize,
@@ -24,23 +32,139 @@ pub enum DistanceModel {
     ExponentClamped = AL_EXPONENT_DISTANCE_CLAMPED as isize,
 }
 
+/// A resampler offered by the implementation. See [`Context::resamplers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resampler {
+    /// The index of the resampler, as used by ``AL_SOURCE_RESAMPLER_SOFT``.
+    pub index: i32,
+    /// The human-readable name of the resampler.
+    pub name: String,
+}
+
 pub(crate) struct ContextInner {
     handle: *mut ALCcontext,
     device: Device,
-    distance_model: RefCell<DistanceModel>,
+    batch_depth: Cell<u32>,
+    pub(crate) event_callback: RefCell<Option<Box<EventCallback>>>,
+    pub(crate) source_generations: Arc<Mutex<SourceGenerations>>,
+    pub(crate) oneshots: RefCell<OneShotPool>,
+    pub(crate) buses: RefCell<BusTree>,
+    pub(crate) tweens: RefCell<Tweens>,
+    pub(crate) snapshots: RefCell<Snapshots>,
+    pub(crate) ducking: RefCell<Ducking>,
+    pub(crate) parameters: RefCell<Parameters>,
+}
+
+impl ContextInner {
+    fn make_current(&self) -> Option<MutexGuard<'static, ()>> {
+        make_context_current(self.handle)
+    }
+}
+
+/// Makes a context current for the thread (if not possible, the entire process).
+pub(crate) fn make_context_current(handle: *mut ALCcontext) -> Option<MutexGuard<'static, ()>> {
+    if set_current_context(handle) {
+        None
+    } else {
+        Some(SINGLE_CONTEXT_LOCK.lock().unwrap())
+    }
+}
+
+/// Makes a context current without locking. Returns whether it's only current for the thread.
+pub(crate) fn set_current_context(handle: *mut ALCcontext) -> bool {
+    // Try for thread first.
+    let function: PFNALCSETTHREADCONTEXTPROC = unsafe {
+        let name = CString::new("alcSetThreadContext").unwrap();
+
+        std::mem::transmute(alcGetProcAddress(
+            ptr::null_mut(),
+            name.as_ptr() as *const ALCchar,
+        ))
+    };
+
+    if let Some(function) = function {
+        unsafe {
+            function(handle);
+        }
+        true
+    } else {
+        // Plan B: Just use alcMakeContextCurrent.
+        // alcMakeContextCurrent should NOT return false.
+        assert_eq!(true as i8, unsafe { alcMakeContextCurrent(handle) });
+        false
+    }
+}
+
+/// The context current on this thread, or the process if contexts can't be current per thread.
+pub(crate) fn current_context() -> *mut ALCcontext {
+    // Try for thread first.
+    let function: PFNALCGETTHREADCONTEXTPROC = unsafe {
+        let name = CString::new("alcGetThreadContext").unwrap();
+
+        std::mem::transmute(alcGetProcAddress(
+            ptr::null_mut(),
+            name.as_ptr() as *const ALCchar,
+        ))
+    };
+
+    if let Some(function) = function {
+        unsafe { function() }
+    } else {
+        // Plan B: Just use alcGetCurrentContext.
+        unsafe { alcGetCurrentContext() }
+    }
 }
 
 impl Drop for ContextInner {
     fn drop(&mut self) {
+        let oneshots = self.oneshots.get_mut().handles();
+        if !oneshots.is_empty() {
+            let _lock = self.make_current();
+            unsafe { alDeleteSources(oneshots.len() as i32, oneshots.as_ptr()) };
+        }
+
         unsafe { alcDestroyContext(self.handle) };
         // TODO: Warn on drop fail.
     }
 }
 
+/// Ends a [`Context::batch`], even if its closure panics.
+struct BatchGuard<'a> {
+    context: &'a Context,
+    /// The depth outside of the batch.
+    depth: u32,
+}
+
+impl BatchGuard<'_> {
+    fn end(&self) -> AllenResult<()> {
+        self.context.inner.batch_depth.set(self.depth);
+
+        if self.depth == 0 {
+            self.context.process_updates()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn finish(self) -> AllenResult<()> {
+        let result = self.end();
+        std::mem::forget(self);
+        result
+    }
+}
+
+impl Drop for BatchGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.end() {
+            println!("WARNING: Batch end failed! {}", err);
+        }
+    }
+}
+
 /// An OpenAL context.
 #[derive(Clone)]
 pub struct Context {
-    inner: Arc<ContextInner>,
+    pub(crate) inner: Arc<ContextInner>,
 }
 
 impl Context {
@@ -54,7 +178,15 @@ impl Context {
                 inner: Arc::new(ContextInner {
                     handle,
                     device,
-                    distance_model: RefCell::new(Default::default()),
+                    batch_depth: Cell::new(0),
+                    event_callback: RefCell::new(None),
+                    source_generations: Default::default(),
+                    oneshots: RefCell::new(Default::default()),
+                    buses: RefCell::new(Default::default()),
+                    tweens: RefCell::new(Default::default()),
+                    snapshots: RefCell::new(Default::default()),
+                    ducking: RefCell::new(Default::default()),
+                    parameters: RefCell::new(Default::default()),
                 }),
             })
         }
@@ -62,52 +194,16 @@ impl Context {
 
     /// Locks the current context into self for the entire thread (if not possible, entire process).
     pub fn make_current(&self) -> Option<MutexGuard<'_, ()>> {
-        // Try for thread first.
-        let function: PFNALCSETTHREADCONTEXTPROC = unsafe {
-            let name = CString::new("alcSetThreadContext").unwrap();
-
-            std::mem::transmute(alcGetProcAddress(
-                ptr::null_mut(),
-                name.as_ptr() as *const ALCchar,
-            ))
-        };
-
-        if let Some(function) = function {
-            unsafe {
-                function(self.inner.handle);
-            }
-            None
-        } else {
-            // Plan B: Just use alcMakeContextCurrent.
-            // alcMakeContextCurrent should NOT return false.
-            assert_eq!(true as i8, unsafe {
-                alcMakeContextCurrent(self.inner.handle)
-            });
-            Some(SINGLE_CONTEXT_LOCK.lock().unwrap())
-        }
+        self.inner.make_current()
     }
 
     pub fn is_current(&self) -> bool {
-        let current_context = {
-            // Try for thread first.
-            let function: PFNALCGETTHREADCONTEXTPROC = unsafe {
-                let name = CString::new("alcGetThreadContext").unwrap();
-
-                std::mem::transmute(alcGetProcAddress(
-                    ptr::null_mut(),
-                    name.as_ptr() as *const ALCchar,
-                ))
-            };
-
-            if let Some(function) = function {
-                unsafe { function() }
-            } else {
-                // Plan B: Just use alcGetCurrentContext.
-                unsafe { alcGetCurrentContext() }
-            }
-        };
+        current_context() == self.inner.handle
+    }
 
-        current_context == self.inner.handle
+    /// The device the context was created under.
+    pub fn device(&self) -> &Device {
+        &self.inner.device
     }
 
     // These functions exist on context because they require a valid context to work.
@@ -132,26 +228,240 @@ impl Context {
         get_string(AL_EXTENSIONS)
     }
 
-    pub fn get_distance_model(&self) -> DistanceModel {
-        self.inner.distance_model.borrow().clone()
+    // AL_SOFT_source_resampler
+    /// Lists every resampler the implementation offers; requires extension ``AL_SOFT_source_resampler``.
+    pub fn resamplers(&self) -> AllenResult<Vec<Resampler>> {
+        let _lock = self.make_current();
+        check_al_extension(&CString::new("AL_SOFT_source_resampler").unwrap())?;
+
+        let count = unsafe { alGetInteger(AL_NUM_RESAMPLERS_SOFT) };
+        check_al_error()?;
+
+        (0..count).map(|index| self.resampler_at(index)).collect()
+    }
+
+    /// The resampler sources use unless told otherwise; requires extension ``AL_SOFT_source_resampler``.
+    pub fn default_resampler(&self) -> AllenResult<Resampler> {
+        let _lock = self.make_current();
+        check_al_extension(&CString::new("AL_SOFT_source_resampler").unwrap())?;
+
+        let index = unsafe { alGetInteger(AL_DEFAULT_RESAMPLER_SOFT) };
+        check_al_error()?;
+
+        self.resampler_at(index)
+    }
+
+    /// Looks up a resampler by its index; requires extension ``AL_SOFT_source_resampler``.
+    pub fn resampler(&self, index: i32) -> AllenResult<Resampler> {
+        let _lock = self.make_current();
+        check_al_extension(&CString::new("AL_SOFT_source_resampler").unwrap())?;
+
+        self.resampler_at(index)
     }
 
-    pub fn set_distance_model(&self, value: DistanceModel) {
-        *self.inner.distance_model.borrow_mut() = value;
+    /// Looks up a resampler by its name (case-insensitive); requires extension ``AL_SOFT_source_resampler``.
+    pub fn resampler_by_name(&self, name: &str) -> AllenResult<Option<Resampler>> {
+        Ok(self
+            .resamplers()?
+            .into_iter()
+            .find(|resampler| resampler.name.eq_ignore_ascii_case(name)))
+    }
+
+    /// Expects the context to be current and the extension to be present.
+    fn resampler_at(&self, index: i32) -> AllenResult<Resampler> {
+        let function: LPALGETSTRINGISOFT = get_proc_address("alGetStringiSOFT");
+        let function = function.expect("AL_SOFT_source_resampler is present");
+
+        let name = unsafe { function(AL_RESAMPLER_NAME_SOFT, index) };
+        check_al_error()?;
+
+        Ok(Resampler {
+            index,
+            name: unsafe { CStr::from_ptr(name) }
+                .to_string_lossy()
+                .to_string(),
+        })
+    }
+
+    pub fn get_distance_model(&self) -> AllenResult<DistanceModel> {
+        let _lock = self.make_current();
+
+        let value = unsafe { alGetInteger(AL_DISTANCE_MODEL) };
+        check_al_error()?;
+
+        FromPrimitive::from_i32(value).ok_or(AllenError::InvalidEnum)
+    }
+
+    /// Sets the distance model of the context. This takes effect immediately, including for sources that are already playing.
+    /// While per-source distance models are enabled (see [`Context::set_source_distance_model_enabled`]), sources use their own model instead.
+    pub fn set_distance_model(&self, value: DistanceModel) -> AllenResult<()> {
+        let _lock = self.make_current();
+
+        unsafe { alDistanceModel(ToPrimitive::to_i32(&value).unwrap()) };
+        check_al_error()
+    }
+
+    pub fn doppler_factor(&self) -> AllenResult<f32> {
+        let _lock = self.make_current();
+
+        let value = unsafe { alGetFloat(AL_DOPPLER_FACTOR) };
+        check_al_error()?;
+
+        Ok(value)
+    }
+
+    /// Scales the doppler effect of every source. `0.0` disables it; the value can't be negative.
+    pub fn set_doppler_factor(&self, value: f32) -> AllenResult<()> {
+        if !(value >= 0.0 && value.is_finite()) {
+            return Err(AllenError::InvalidValue);
+        }
+
+        let _lock = self.make_current();
+
+        unsafe { alDopplerFactor(value) };
+        check_al_error()
+    }
+
+    pub fn speed_of_sound(&self) -> AllenResult<f32> {
+        let _lock = self.make_current();
+
+        let value = unsafe { alGetFloat(AL_SPEED_OF_SOUND) };
+        check_al_error()?;
+
+        Ok(value)
+    }
+
+    /// Sets the speed of sound in world units per second, used for the doppler effect. The value must be positive.
+    /// Defaults to 343.3, which is meters per second in air.
+    pub fn set_speed_of_sound(&self, value: f32) -> AllenResult<()> {
+        if !(value > 0.0 && value.is_finite()) {
+            return Err(AllenError::InvalidValue);
+        }
+
+        let _lock = self.make_current();
+
+        unsafe { alSpeedOfSound(value) };
+        check_al_error()
+    }
+
+    // AL_EXT_source_distance_model
+    /// Whether sources use their own distance model rather than the context's. See [`Source::set_distance_model`].
+    pub fn is_source_distance_model_enabled(&self) -> AllenResult<bool> {
+        let _lock = self.make_current();
+        check_al_extension(&CString::new("AL_EXT_source_distance_model").unwrap())?;
+
+        let result = unsafe { alIsEnabled(AL_SOURCE_DISTANCE_MODEL) };
+        check_al_error()?;
+
+        Ok(result != 0)
+    }
+
+    /// Makes sources use their own distance model rather than the context's; requires extension ``AL_EXT_source_distance_model``.
+    /// While enabled, sources that never had a model set (see [`Source::set_distance_model`]) use [`DistanceModel::InverseClamped`],
+    /// and [`Context::set_distance_model`] affects none of them.
+    pub fn set_source_distance_model_enabled(&self, value: bool) -> AllenResult<()> {
+        let _lock = self.make_current();
+        check_al_extension(&CString::new("AL_EXT_source_distance_model").unwrap())?;
+
+        unsafe {
+            if value {
+                alEnable(AL_SOURCE_DISTANCE_MODEL);
+            } else {
+                alDisable(AL_SOURCE_DISTANCE_MODEL);
+            }
+        }
+        check_al_error()
     }
 
     pub fn listener(&self) -> Listener {
         Listener::new(self.clone())
     }
 
+    /// Creates a buffer on the context's device. See [`Device::new_buffer`].
     pub fn new_buffer(&self) -> AllenResult<Buffer> {
-        Buffer::new(self.clone())
+        self.device().new_buffer()
     }
 
     pub fn new_source(&self) -> AllenResult<Source> {
         Source::new(self.clone())
     }
 
+    /// Creates a voice manager that plays voices on up to `max_sources` sources.
+    /// See [`Device::mono_sources`] and [`Device::stereo_sources`] for how many sources the device allows.
+    pub fn new_voice_manager(&self, max_sources: usize) -> AllenResult<VoiceManager> {
+        VoiceManager::new(self.clone(), max_sources)
+    }
+
+    /// Plays every source at once, so that they start on the same sample.
+    pub fn play_all(&self, sources: &[&Source]) -> AllenResult<()> {
+        let handles = self.source_handles(sources)?;
+        let _lock = self.make_current();
+
+        unsafe { alSourcePlayv(handles.len() as i32, handles.as_ptr()) };
+        check_al_error()
+    }
+
+    /// Plays every source at once when the device clock reaches `start_time` (see [`Device::clock`]).
+    /// Requires extension ``AL_SOFT_source_start_delay``.
+    pub fn play_all_at(&self, sources: &[&Source], start_time: Duration) -> AllenResult<()> {
+        let handles = self.source_handles(sources)?;
+        let _lock = self.make_current();
+        check_al_extension(&CString::new("AL_SOFT_source_start_delay").unwrap())?;
+
+        let function: LPALSOURCEPLAYATTIMEVSOFT = get_proc_address("alSourcePlayAtTimevSOFT");
+        let function = function.expect("AL_SOFT_source_start_delay is present");
+
+        unsafe {
+            function(
+                handles.len() as i32,
+                handles.as_ptr(),
+                start_time.as_nanos() as i64,
+            )
+        };
+        check_al_error()
+    }
+
+    /// Pauses every source at once.
+    pub fn pause_all(&self, sources: &[&Source]) -> AllenResult<()> {
+        let handles = self.source_handles(sources)?;
+        let _lock = self.make_current();
+
+        unsafe { alSourcePausev(handles.len() as i32, handles.as_ptr()) };
+        check_al_error()
+    }
+
+    /// Stops every source at once.
+    pub fn stop_all(&self, sources: &[&Source]) -> AllenResult<()> {
+        let handles = self.source_handles(sources)?;
+        let _lock = self.make_current();
+
+        unsafe { alSourceStopv(handles.len() as i32, handles.as_ptr()) };
+        check_al_error()
+    }
+
+    /// Rewinds every source at once.
+    pub fn rewind_all(&self, sources: &[&Source]) -> AllenResult<()> {
+        let handles = self.source_handles(sources)?;
+        let _lock = self.make_current();
+
+        unsafe { alSourceRewindv(handles.len() as i32, handles.as_ptr()) };
+        check_al_error()
+    }
+
+    /// Collects the handles of the sources, making sure they all belong to this context.
+    pub(crate) fn source_handles(&self, sources: &[&Source]) -> AllenResult<Vec<u32>> {
+        sources
+            .iter()
+            .map(|source| {
+                if Arc::ptr_eq(&source.context().inner, &self.inner) {
+                    Ok(source.handle())
+                } else {
+                    Err(AllenError::InvalidContext)
+                }
+            })
+            .collect()
+    }
+
     pub fn suspend(&self) -> AllenResult<()> {
         let _lock = self.make_current();
         unsafe {
@@ -169,4 +479,66 @@ impl Context {
         self.inner.device.check_alc_error()?;
         Ok(())
     }
+
+    /// Advances everything the context drives over time by `delta`: [`crate::Tween`]s move along, [`crate::Snapshot`]s fade in and out,
+    /// [`crate::DuckingRule`]s follow their trigger sources, changed game parameters are pushed to the properties bound to them,
+    /// and finished one-shots return their sources to the pool.
+    /// Meant to be called once per frame.
+    pub fn update(&self, delta: Duration) -> AllenResult<()> {
+        self.update_tweens(delta)?;
+        self.update_snapshots(delta)?;
+        self.update_ducking(delta)?;
+        self.update_parameters()?;
+        self.recycle_oneshots()
+    }
+
+    /// Runs `f` with property updates deferred, so everything set inside the closure is applied atomically in the same mix.
+    /// Uses ``AL_SOFT_deferred_updates`` when present, otherwise falls back to [`Context::suspend`] and [`Context::process`].
+    /// Updates are processed even if `f` fails. Nested batches are applied when the outermost one ends.
+    pub fn batch<T>(&self, f: impl FnOnce(&Context) -> AllenResult<T>) -> AllenResult<T> {
+        let depth = self.inner.batch_depth.get();
+        if depth == 0 {
+            self.defer_updates()?;
+        }
+        self.inner.batch_depth.set(depth + 1);
+
+        let guard = BatchGuard {
+            context: self,
+            depth,
+        };
+        let result = f(self);
+        guard.finish()?;
+
+        result
+    }
+
+    fn defer_updates(&self) -> AllenResult<()> {
+        let lock = self.make_current();
+
+        if is_extension_present(&CString::new("AL_SOFT_deferred_updates").unwrap())? {
+            let function: LPALDEFERUPDATESSOFT = get_proc_address("alDeferUpdatesSOFT");
+            let function = function.expect("AL_SOFT_deferred_updates is present");
+
+            unsafe { function() };
+            check_al_error()
+        } else {
+            drop(lock);
+            self.suspend()
+        }
+    }
+
+    fn process_updates(&self) -> AllenResult<()> {
+        let lock = self.make_current();
+
+        if is_extension_present(&CString::new("AL_SOFT_deferred_updates").unwrap())? {
+            let function: LPALPROCESSUPDATESSOFT = get_proc_address("alProcessUpdatesSOFT");
+            let function = function.expect("AL_SOFT_deferred_updates is present");
+
+            unsafe { function() };
+            check_al_error()
+        } else {
+            drop(lock);
+            self.process()
+        }
+    }
 }
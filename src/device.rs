@@ -1,17 +1,60 @@
-use crate::{sys::*, AllenError, AllenResult, Context};
-use std::{ffi::CStr, ptr, sync::Arc};
+use crate::{
+    context::{current_context, make_context_current, set_current_context},
+    sys::*,
+    AllenError, AllenResult, Buffer, Channels, Context, Sample, StaticBuffer,
+};
+use std::{
+    any::Any,
+    cell::OnceCell,
+    ffi::{CStr, CString},
+    ptr,
+    sync::{Arc, MutexGuard},
+    time::Duration,
+};
 
 pub(crate) struct DeviceInner {
     pub(crate) handle: *mut ALCdevice,
+    /// A context of our own, made current to work with buffers no matter which contexts the user has.
+    /// Created along with the first buffer.
+    buffer_context: OnceCell<*mut ALCcontext>,
 }
 
 impl Drop for DeviceInner {
     fn drop(&mut self) {
+        // Buffers hold on to the device, so they're all gone by now.
+        // The buffer context has to be destroyed before the device is closed, and can't be current while it is.
+        if let Some(&context) = self.buffer_context.get() {
+            if current_context() == context {
+                let _lock = make_context_current(ptr::null_mut());
+            }
+
+            unsafe { alcDestroyContext(context) };
+        }
+
         unsafe { alcCloseDevice(self.handle) };
     }
 }
 
+/// Keeps the buffer context current. Makes the context that was current before current again when dropped.
+pub(crate) struct BufferContextGuard {
+    previous: *mut ALCcontext,
+    /// Released after the previous context is back.
+    _lock: Option<MutexGuard<'static, ()>>,
+}
+
+impl Drop for BufferContextGuard {
+    fn drop(&mut self) {
+        set_current_context(self.previous);
+    }
+}
+
 /// An OpenAL device.
+///
+/// Creating the first buffer also creates a context of the device's own, which buffers make current
+/// (for the calling thread if ``ALC_EXT_thread_local_context`` is present, for the whole process otherwise)
+/// so that they don't depend on any [`Context`]. The context that was current before is made current again afterwards.
+/// The buffer context takes up a slot of the device's mixer like any other, and is destroyed along with the device,
+/// once every clone of it and every buffer and context of it is dropped.
 #[derive(Clone)]
 pub struct Device {
     pub(crate) inner: Arc<DeviceInner>,
@@ -27,7 +70,10 @@ impl Device {
             None
         } else {
             Some(Device {
-                inner: Arc::new(DeviceInner { handle }),
+                inner: Arc::new(DeviceInner {
+                    handle,
+                    buffer_context: OnceCell::new(),
+                }),
             })
         }
     }
@@ -39,11 +85,161 @@ impl Device {
             .unwrap()
     }
 
+    /// The maximum amount of mono sources contexts of the device can play at once.
+    pub fn mono_sources(&self) -> AllenResult<i32> {
+        self.get_integer(ALC_MONO_SOURCES)
+    }
+
+    /// The maximum amount of stereo sources contexts of the device can play at once.
+    pub fn stereo_sources(&self) -> AllenResult<i32> {
+        self.get_integer(ALC_STEREO_SOURCES)
+    }
+
+    fn get_integer(&self, param: i32) -> AllenResult<i32> {
+        let mut value = 0;
+        unsafe { alcGetIntegerv(self.inner.handle, param, 1, &mut value) };
+        self.check_alc_error()?;
+
+        Ok(value)
+    }
+
     /// Creates a context under the device.
     pub fn create_context(&self) -> AllenResult<Context> {
         Context::new(self.clone())
     }
 
+    /// Creates a buffer. It can be attached to sources of any context of the device, and it outlives them.
+    pub fn new_buffer(&self) -> AllenResult<Buffer> {
+        if self.inner.buffer_context.get().is_none() {
+            let context = unsafe { alcCreateContext(self.inner.handle, ptr::null()) };
+
+            if context.is_null() {
+                return Err(self.check_alc_error().expect_err("handle is null"));
+            }
+
+            let _ = self.inner.buffer_context.set(context);
+        }
+
+        Buffer::new(self.clone())
+    }
+
+    /// Runs `f` with a buffer playing `data` in place. See [`StaticBuffer`].
+    /// The buffer is deleted once `f` returns, after stopping and detaching every source still playing it.
+    pub fn with_static_buffer<T: Sample, R>(
+        &self,
+        data: &[T],
+        channels: Channels,
+        sample_rate: i32,
+        f: impl FnOnce(&StaticBuffer) -> R,
+    ) -> AllenResult<R> {
+        // `f` only gets to borrow the buffer, so it's dropped right here, before the data can go away.
+        let buffer = unsafe { self.new_static_buffer(data, channels, sample_rate)? };
+        Ok(f(&buffer))
+    }
+
+    /// Creates a buffer playing `data` in place. The data is borrowed for as long as the buffer exists.
+    /// See [`StaticBuffer`].
+    ///
+    /// # Safety
+    /// The buffer has to be dropped before the data is freed or moved. The borrow alone doesn't ensure that,
+    /// as a buffer can be leaked (e.g. through [`std::mem::forget`]) without detaching the sources still reading the data.
+    /// [`Device::with_static_buffer`] and [`Device::new_shared_static_buffer`] are safe alternatives.
+    pub unsafe fn new_static_buffer<'a, T: Sample>(
+        &self,
+        data: &'a [T],
+        channels: Channels,
+        sample_rate: i32,
+    ) -> AllenResult<StaticBuffer<'a>> {
+        StaticBuffer::new(self.new_buffer()?, data, channels, sample_rate, None)
+    }
+
+    /// Creates a buffer playing shared `data` in place. The buffer keeps the data alive for as long as it exists,
+    /// and forever if it's leaked.
+    pub fn new_shared_static_buffer<T: Sample>(
+        &self,
+        data: Arc<[T]>,
+        channels: Channels,
+        sample_rate: i32,
+    ) -> AllenResult<StaticBuffer<'static>> {
+        let owner: Box<dyn Any> = Box::new(data.clone());
+
+        // The buffer holds on to a reference, so the memory stays put.
+        unsafe {
+            StaticBuffer::new(
+                self.new_buffer()?,
+                &data,
+                channels,
+                sample_rate,
+                Some(owner),
+            )
+        }
+    }
+
+    /// Makes the device's buffer context current, until the guard is dropped. Expects a buffer to have been created.
+    pub(crate) fn make_current(&self) -> BufferContextGuard {
+        let context = self.inner.buffer_context.get();
+        let previous = current_context();
+
+        BufferContextGuard {
+            previous,
+            _lock: make_context_current(*context.expect("buffers create the buffer context")),
+        }
+    }
+
+    /// Whether both are the same device.
+    pub(crate) fn is_same(&self, other: &Device) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    // ALC_SOFT_device_clock
+    /// The device clock: the amount of audio the device has processed since it was opened.
+    pub fn clock(&self) -> AllenResult<Duration> {
+        let [clock] = self.get_integer64(ALC_DEVICE_CLOCK_SOFT)?;
+        Ok(Duration::from_nanos(clock.max(0) as u64))
+    }
+
+    /// The output latency of the device.
+    pub fn latency(&self) -> AllenResult<Duration> {
+        let [latency] = self.get_integer64(ALC_DEVICE_LATENCY_SOFT)?;
+        Ok(Duration::from_nanos(latency.max(0) as u64))
+    }
+
+    /// The device clock and the output latency, sampled together.
+    pub fn clock_with_latency(&self) -> AllenResult<(Duration, Duration)> {
+        let [clock, latency] = self.get_integer64(ALC_DEVICE_CLOCK_LATENCY_SOFT)?;
+        Ok((
+            Duration::from_nanos(clock.max(0) as u64),
+            Duration::from_nanos(latency.max(0) as u64),
+        ))
+    }
+
+    fn get_integer64<const N: usize>(&self, param: i32) -> AllenResult<[i64; N]> {
+        self.check_alc_extension(&CString::new("ALC_SOFT_device_clock").unwrap())?;
+
+        let function: LPALCGETINTEGER64VSOFT = self.get_proc_address("alcGetInteger64vSOFT");
+        let function = function.expect("ALC_SOFT_device_clock is present");
+
+        let mut value = [0; N];
+        unsafe { function(self.inner.handle, param, N as i32, value.as_mut_ptr()) };
+        self.check_alc_error()?;
+
+        Ok(value)
+    }
+
+    /// Loads an ALC extension function. `T` must be the matching `LPALC*` function pointer type.
+    pub(crate) fn get_proc_address<T>(&self, name: &str) -> T {
+        assert_eq!(
+            std::mem::size_of::<T>(),
+            std::mem::size_of::<*mut ALCvoid>()
+        );
+
+        let name = CString::new(name).unwrap();
+        unsafe {
+            let address = alcGetProcAddress(self.inner.handle, name.as_ptr());
+            std::mem::transmute_copy(&address)
+        }
+    }
+
     pub fn is_extension_present(&self, name: &CStr) -> AllenResult<bool> {
         let result = unsafe { alcIsExtensionPresent(self.inner.handle, name.as_ptr()) };
         self.check_alc_error()?;
@@ -0,0 +1,141 @@
+use crate::{check_al_error, sys::*, tween::fade, AllenError, AllenResult, BusId, Context};
+use std::time::Duration;
+
+/// Attenuates some buses while a source is playing on another, e.g. to dip music and ambience under dialogue.
+/// Added with [`Context::add_ducking_rule`], and followed by [`Context::update`], which checks the state of the trigger bus's sources.
+///
+/// With the ``serde`` feature, rules can be loaded from data files, with attack and release times in seconds.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuckingRule {
+    /// The name of the bus whose sources (including those on buses inside it) trigger the rule while playing.
+    pub trigger: String,
+    /// The names of the buses to attenuate.
+    pub targets: Vec<String>,
+    /// How far the targets are attenuated, in decibels. Must not be negative.
+    pub attenuation_db: f32,
+    /// How long it takes to reach the full attenuation once triggered.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::snapshot::secs"))]
+    pub attack: Duration,
+    /// How long it takes to recover once no trigger source is playing anymore.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::snapshot::secs"))]
+    pub release: Duration,
+}
+
+/// Identifies a ducking rule added to a [`Context`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DuckingId(u64);
+
+struct ActiveRule {
+    id: DuckingId,
+    rule: DuckingRule,
+    trigger: BusId,
+    targets: Vec<BusId>,
+    /// How far the attenuation has been applied, from 0 to 1.
+    level: f32,
+}
+
+#[derive(Default)]
+pub(crate) struct Ducking {
+    next_id: u64,
+    rules: Vec<ActiveRule>,
+}
+
+impl Context {
+    /// Adds a ducking rule. Fails with [`AllenError::InvalidName`] if a bus doesn't exist,
+    /// and with [`AllenError::InvalidValue`] if the attenuation is negative.
+    pub fn add_ducking_rule(&self, rule: DuckingRule) -> AllenResult<DuckingId> {
+        if !(rule.attenuation_db >= 0.0 && rule.attenuation_db.is_finite()) {
+            return Err(AllenError::InvalidValue);
+        }
+
+        let (trigger, targets) = {
+            let tree = self.inner.buses.borrow();
+            let find = |name: &String| tree.find(name).ok_or(AllenError::InvalidName);
+
+            (
+                find(&rule.trigger)?,
+                rule.targets.iter().map(find).collect::<AllenResult<_>>()?,
+            )
+        };
+
+        let mut ducking = self.inner.ducking.borrow_mut();
+
+        let id = DuckingId(ducking.next_id);
+        ducking.next_id += 1;
+
+        ducking.rules.push(ActiveRule {
+            id,
+            rule,
+            trigger,
+            targets,
+            level: 0.0,
+        });
+
+        Ok(id)
+    }
+
+    /// Removes a ducking rule. Its targets recover right away, without waiting for the release time.
+    pub fn remove_ducking_rule(&self, id: DuckingId) -> AllenResult<()> {
+        self.inner
+            .ducking
+            .borrow_mut()
+            .rules
+            .retain(|active| active.id != id);
+
+        self.update_ducking(Duration::ZERO)
+    }
+
+    /// Whether the rule is currently attenuating its targets, including while they recover.
+    pub fn is_ducking(&self, id: DuckingId) -> bool {
+        self.inner
+            .ducking
+            .borrow()
+            .rules
+            .iter()
+            .any(|active| active.id == id && active.level > 0.0)
+    }
+
+    /// Moves the attenuation of every rule towards its target, depending on whether its trigger bus has a playing source.
+    pub(crate) fn update_ducking(&self, delta: Duration) -> AllenResult<()> {
+        let mut ducking = self.inner.ducking.borrow_mut();
+
+        let _lock = self.make_current();
+        let mut tree = self.inner.buses.borrow_mut();
+
+        for active in &mut ducking.rules {
+            let mut is_triggered = false;
+
+            for handle in tree.members_within(active.trigger) {
+                let mut state = 0;
+                unsafe { alGetSourcei(handle, AL_SOURCE_STATE, &mut state) };
+                check_al_error()?;
+
+                if state == AL_PLAYING {
+                    is_triggered = true;
+                    break;
+                }
+            }
+
+            active.level = if is_triggered {
+                fade(active.level, 1.0, delta, active.rule.attack)
+            } else {
+                fade(active.level, 0.0, delta, active.rule.release)
+            };
+        }
+
+        // Rules sharing a target add up, in decibels.
+        let mut attenuations = vec![0.0; tree.len()];
+        for active in &ducking.rules {
+            for target in &active.targets {
+                attenuations[target.0] += active.rule.attenuation_db * active.level;
+            }
+        }
+
+        for (index, attenuation) in attenuations.into_iter().enumerate() {
+            tree.set_duck_gain(BusId(index), 10f32.powf(-attenuation / 20.0));
+        }
+
+        tree.apply()
+    }
+}
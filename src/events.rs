@@ -0,0 +1,195 @@
+use crate::{
+    check_al_error, check_al_extension, get_proc_address, sys::*, AllenResult, Context,
+    SourceGenerations, SourceId, SourceState,
+};
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::{FromPrimitive, ToPrimitive};
+use std::{
+    ffi::{c_void, CString},
+    slice,
+    sync::{mpsc, Arc, Mutex},
+};
+
+/// The kinds of [`Event`] a context can report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EventType {
+    /// See [`Event::SourceStateChanged`].
+    SourceStateChanged = AL_EVENT_TYPE_SOURCE_STATE_CHANGED_SOFT as isize,
+    /// See [`Event::BufferCompleted`].
+    BufferCompleted = AL_EVENT_TYPE_BUFFER_COMPLETED_SOFT as isize,
+    /// See [`Event::Disconnected`].
+    Disconnected = AL_EVENT_TYPE_DISCONNECTED_SOFT as isize,
+}
+
+impl EventType {
+    /// Every event type.
+    pub const ALL: [EventType; 3] = [
+        EventType::SourceStateChanged,
+        EventType::BufferCompleted,
+        EventType::Disconnected,
+    ];
+}
+
+/// An asynchronous event reported by a [`Context`]. Requires extension ``AL_SOFT_events``.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A source changed state, e.g. it stopped after reaching the end of its buffers.
+    SourceStateChanged {
+        source: SourceId,
+        state: SourceState,
+    },
+    /// A source finished playing one or more of its queued buffers.
+    BufferCompleted { source: SourceId, count: u32 },
+    /// The device was disconnected and can no longer play audio.
+    Disconnected { message: String },
+}
+
+impl Event {
+    /// The source the event is about, if any. Compare it with [`crate::Source::id`].
+    pub fn source(&self) -> Option<SourceId> {
+        match self {
+            Event::SourceStateChanged { source, .. } | Event::BufferCompleted { source, .. } => {
+                Some(*source)
+            }
+            Event::Disconnected { .. } => None,
+        }
+    }
+
+    pub fn event_type(&self) -> EventType {
+        match self {
+            Event::SourceStateChanged { .. } => EventType::SourceStateChanged,
+            Event::BufferCompleted { .. } => EventType::BufferCompleted,
+            Event::Disconnected { .. } => EventType::Disconnected,
+        }
+    }
+}
+
+/// Called from OpenAL's event thread, hence the mutexes.
+pub(crate) struct EventCallback {
+    callback: Mutex<Box<dyn FnMut(Event) + Send>>,
+    /// Resolves source handles into ids when events are delivered.
+    sources: Arc<Mutex<SourceGenerations>>,
+}
+
+unsafe extern "C" fn event_proc(
+    event_type: ALenum,
+    object: ALuint,
+    param: ALuint,
+    length: ALsizei,
+    message: *const ALchar,
+    user_param: *mut c_void,
+) {
+    let callback = &*(user_param as *const EventCallback);
+
+    // Events about sources that have been deleted since are dropped.
+    let source = || match callback.sources.lock() {
+        Ok(sources) => sources.id(object),
+        Err(_) => None,
+    };
+
+    let event = match FromPrimitive::from_i32(event_type) {
+        Some(EventType::SourceStateChanged) => match (source(), FromPrimitive::from_u32(param)) {
+            (Some(source), Some(state)) => Event::SourceStateChanged { source, state },
+            _ => return,
+        },
+        Some(EventType::BufferCompleted) => match source() {
+            Some(source) => Event::BufferCompleted {
+                source,
+                count: param,
+            },
+            None => return,
+        },
+        Some(EventType::Disconnected) => Event::Disconnected {
+            message: if message.is_null() {
+                String::new()
+            } else {
+                let message = slice::from_raw_parts(message as *const u8, length.max(0) as usize);
+                String::from_utf8_lossy(message).to_string()
+            },
+        },
+        None => return,
+    };
+
+    if let Ok(mut callback) = callback.callback.lock() {
+        callback(event);
+    }
+}
+
+impl Context {
+    /// Delivers the given event types to `callback`, replacing any previous callback.
+    /// The callback runs on OpenAL's event thread, so it should return quickly.
+    pub fn set_event_callback<F>(&self, types: &[EventType], callback: F) -> AllenResult<()>
+    where
+        F: FnMut(Event) + Send + 'static,
+    {
+        let callback = Box::new(EventCallback {
+            callback: Mutex::new(Box::new(callback)),
+            sources: self.inner.source_generations.clone(),
+        });
+
+        let _lock = self.make_current();
+        check_al_extension(&CString::new("AL_SOFT_events").unwrap())?;
+
+        let function: LPALEVENTCALLBACKSOFT = get_proc_address("alEventCallbackSOFT");
+        let function = function.expect("AL_SOFT_events is present");
+
+        unsafe {
+            function(
+                Some(event_proc),
+                &*callback as *const EventCallback as *mut c_void,
+            )
+        };
+        check_al_error()?;
+
+        // Once the new callback is registered, OpenAL no longer uses the old one.
+        *self.inner.event_callback.borrow_mut() = Some(callback);
+
+        Self::control_events(&EventType::ALL, false)?;
+        Self::control_events(types, true)
+    }
+
+    /// Stops delivering events and removes the callback set with [`Context::set_event_callback`].
+    pub fn clear_event_callback(&self) -> AllenResult<()> {
+        let _lock = self.make_current();
+        check_al_extension(&CString::new("AL_SOFT_events").unwrap())?;
+
+        Self::control_events(&EventType::ALL, false)?;
+
+        let function: LPALEVENTCALLBACKSOFT = get_proc_address("alEventCallbackSOFT");
+        let function = function.expect("AL_SOFT_events is present");
+
+        unsafe { function(None, std::ptr::null_mut()) };
+        check_al_error()?;
+
+        *self.inner.event_callback.borrow_mut() = None;
+
+        Ok(())
+    }
+
+    /// Delivers the given event types through a channel, replacing any previous callback.
+    pub fn events(&self, types: &[EventType]) -> AllenResult<mpsc::Receiver<Event>> {
+        let (sender, receiver) = mpsc::channel();
+
+        self.set_event_callback(types, move |event| {
+            // The receiver being gone just means nobody is listening anymore.
+            let _ = sender.send(event);
+        })?;
+
+        Ok(receiver)
+    }
+
+    /// Expects the context to be current and the extension to be present.
+    fn control_events(types: &[EventType], enable: bool) -> AllenResult<()> {
+        let function: LPALEVENTCONTROLSOFT = get_proc_address("alEventControlSOFT");
+        let function = function.expect("AL_SOFT_events is present");
+
+        let types = types
+            .iter()
+            .map(|event_type| ToPrimitive::to_i32(event_type).unwrap())
+            .collect::<Vec<_>>();
+
+        unsafe { function(types.len() as i32, types.as_ptr(), enable as ALboolean) };
+        check_al_error()
+    }
+}
@@ -0,0 +1,59 @@
+//! Channel layouts known at compile time, for [`crate::Buffer::upload`].
+
+use crate::{Channels, Sample};
+
+/// A channel layout known at compile time, along with the type of its sample frames.
+pub trait ChannelLayout: sealed::Sealed {
+    const CHANNELS: Channels;
+
+    /// One sample per channel, e.g. `[i16; 2]` for 16-bit [`Stereo`].
+    type Frame<T: Sample>: Copy;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! layouts {
+    ($($(#[$meta:meta])* $name:ident = $count:literal;)*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub struct $name;
+
+            impl sealed::Sealed for $name {}
+
+            impl ChannelLayout for $name {
+                const CHANNELS: Channels = Channels::$name;
+                type Frame<T: Sample> = [T; $count];
+            }
+        )*
+    };
+}
+
+layouts! {
+    /// See [`Channels::Mono`].
+    Mono = 1;
+    /// See [`Channels::Stereo`].
+    Stereo = 2;
+    /// See [`Channels::Rear`].
+    Rear = 2;
+    /// See [`Channels::Quad`].
+    Quad = 4;
+    /// See [`Channels::Surround51`].
+    Surround51 = 6;
+    /// See [`Channels::Surround61`].
+    Surround61 = 7;
+    /// See [`Channels::Surround71`].
+    Surround71 = 8;
+    /// First-order [`Channels::BFormat2D`].
+    BFormat2D = 3;
+    /// First-order [`Channels::BFormat3D`].
+    BFormat3D = 4;
+    /// See [`Channels::Uhj2`].
+    Uhj2 = 2;
+    /// See [`Channels::Uhj3`].
+    Uhj3 = 3;
+    /// See [`Channels::Uhj4`].
+    Uhj4 = 4;
+}
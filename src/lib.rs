@@ -1,21 +1,43 @@
 mod buffer;
+mod bus;
+mod clip;
 mod context;
 mod device;
+mod ducking;
+mod events;
 #[macro_use]
 mod properties;
+pub mod layout;
 mod listener;
+mod oneshot;
+mod parameters;
+mod snapshot;
 mod source;
+mod static_buffer;
 pub(crate) mod sys;
+mod tween;
+mod voices;
 
 use crate::sys::*;
 pub use buffer::*;
+pub use bus::*;
+pub use clip::*;
 pub use context::*;
 pub use device::*;
+pub use ducking::*;
+pub use events::*;
+pub use layout::ChannelLayout;
 pub use listener::*;
+pub use oneshot::*;
+pub use parameters::*;
 pub(crate) use properties::*;
+pub use snapshot::*;
 pub use source::*;
-use std::ffi::CStr;
+pub use static_buffer::*;
+use std::ffi::{c_void, CStr, CString};
 use thiserror::Error;
+pub use tween::*;
+pub use voices::*;
 
 /// For whatever reason, macros which take type parameters can't accept "[f32; 3]"
 pub(crate) type Float3 = [f32; 3];
@@ -29,6 +51,21 @@ pub struct Orientation {
     pub at: Float3,
 }
 
+impl Orientation {
+    /// The layout AL_ORIENTATION takes: `at`, then `up`.
+    pub(crate) fn to_al(self) -> [f32; 6] {
+        let (at, up) = (self.at, self.up);
+        [at[0], at[1], at[2], up[0], up[1], up[2]]
+    }
+
+    pub(crate) fn from_al(value: [f32; 6]) -> Self {
+        Orientation {
+            up: [value[3], value[4], value[5]],
+            at: [value[0], value[1], value[2]],
+        }
+    }
+}
+
 /// An OpenAL error.
 #[derive(Error, Debug)]
 pub enum AllenError {
@@ -78,6 +115,17 @@ pub(crate) fn get_string(param: ALenum) -> &'static str {
         .unwrap() // Unwrap is justified because from what I understand, this SHOULD be a valid string.
 }
 
+/// Loads an AL extension function. `T` must be the matching `LPAL*` function pointer type.
+pub(crate) fn get_proc_address<T>(name: &str) -> T {
+    assert_eq!(std::mem::size_of::<T>(), std::mem::size_of::<*mut c_void>());
+
+    let name = CString::new(name).unwrap();
+    unsafe {
+        let address = alGetProcAddress(name.as_ptr());
+        std::mem::transmute_copy(&address)
+    }
+}
+
 pub fn is_extension_present(name: &CStr) -> AllenResult<bool> {
     let result = unsafe { alIsExtensionPresent(name.as_ptr()) };
     check_al_error()?;
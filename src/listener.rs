@@ -62,17 +62,17 @@ impl PropertiesContainer<Orientation> for Listener {
     fn get(&self, param: i32) -> AllenResult<Orientation> {
         let _lock = self.context.make_current();
 
-        let mut value = Orientation::default();
-        unsafe { alGetListenerfv(param, &mut value as *mut Orientation as *mut f32) };
+        let mut value = [0.0; 6];
+        unsafe { alGetListenerfv(param, value.as_mut_ptr()) };
         check_al_error()?;
 
-        Ok(value)
+        Ok(Orientation::from_al(value))
     }
 
     fn set(&self, param: i32, value: Orientation) -> AllenResult<()> {
         let _lock = self.context.make_current();
 
-        unsafe { alListenerfv(param, &value as *const Orientation as *const f32) };
+        unsafe { alListenerfv(param, value.to_al().as_ptr()) };
         check_al_error()?;
 
         Ok(())
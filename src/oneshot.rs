@@ -0,0 +1,224 @@
+use crate::{
+    check_al_error, sys::*, AllenResult, Buffer, Context, Float3, Source, SourceState, VoiceParams,
+};
+use std::rc::Rc;
+
+struct Playing {
+    priority: i32,
+    /// Used to find the oldest one-shot when one has to be stolen.
+    started: u64,
+    /// Kept alive while it's attached to the source.
+    buffer: Rc<Buffer>,
+}
+
+struct Entry {
+    handle: u32,
+    generation: u32,
+    playing: Option<Playing>,
+}
+
+/// The sources one-shots are played on. Sources are kept as raw handles, since a [`Source`] would keep the context alive.
+#[derive(Default)]
+pub(crate) struct OneShotPool {
+    entries: Vec<Entry>,
+    started: u64,
+}
+
+impl OneShotPool {
+    pub(crate) fn handles(&self) -> Vec<u32> {
+        self.entries.iter().map(|entry| entry.handle).collect()
+    }
+
+    /// Marks an entry as free, invalidating the [`OneShot`]s referring to it.
+    /// Returns the buffer it played, which has to be detached from the source before it's dropped,
+    /// and dropped without the context being current (see [`Context::make_current`]).
+    fn release(&mut self, index: usize) -> Option<Rc<Buffer>> {
+        let entry = &mut self.entries[index];
+        entry.generation = entry.generation.wrapping_add(1);
+        entry.playing.take().map(|playing| playing.buffer)
+    }
+}
+
+/// A handle to a sound started with [`Context::play_oneshot`].
+/// Once the sound finishes, the handle goes stale and its methods do nothing.
+#[derive(Clone)]
+pub struct OneShot {
+    context: Context,
+    index: usize,
+    generation: u32,
+}
+
+impl OneShot {
+    /// Whether the sound is still playing.
+    pub fn is_playing(&self) -> AllenResult<bool> {
+        let mut is_playing = false;
+        self.with_source(|source| {
+            is_playing = source.state()? == SourceState::Playing;
+            Ok(())
+        })?;
+        Ok(is_playing)
+    }
+
+    /// Stops the sound early. Its source goes back to the pool.
+    pub fn stop(&self) -> AllenResult<()> {
+        self.with_source(|source| source.stop())?;
+        self.context.recycle_oneshots()
+    }
+
+    pub fn set_gain(&self, value: f32) -> AllenResult<()> {
+        self.with_source(|source| source.set_gain(value))
+    }
+
+    pub fn set_pitch(&self, value: f32) -> AllenResult<()> {
+        self.with_source(|source| source.set_pitch(value))
+    }
+
+    pub fn set_position(&self, value: Float3) -> AllenResult<()> {
+        self.with_source(|source| source.set_position(value))
+    }
+
+    pub fn set_velocity(&self, value: Float3) -> AllenResult<()> {
+        self.with_source(|source| source.set_velocity(value))
+    }
+
+    /// Runs `f` on the one-shot's source, if the one-shot hasn't finished yet.
+    fn with_source(&self, f: impl FnOnce(&Source) -> AllenResult<()>) -> AllenResult<()> {
+        let handle = {
+            let pool = self.context.inner.oneshots.borrow();
+            let entry = &pool.entries[self.index];
+
+            if entry.generation != self.generation || entry.playing.is_none() {
+                return Ok(());
+            }
+
+            entry.handle
+        };
+
+        self.context.with_borrowed_source(handle, f)
+    }
+}
+
+impl Context {
+    /// Plays a buffer on a pooled source, which goes back to the pool once the sound stops.
+    /// The buffer is kept alive until then.
+    /// The returned handle can be ignored, or used to stop the sound early or tweak it while it plays.
+    ///
+    /// If no more sources can be created, the one-shot with the lowest priority (the oldest one, among equals) is cut off,
+    /// as long as its priority isn't higher than the new one's.
+    pub fn play_oneshot(&self, buffer: Rc<Buffer>, params: VoiceParams) -> AllenResult<OneShot> {
+        self.recycle_oneshots()?;
+
+        let free = {
+            let pool = self.inner.oneshots.borrow();
+            pool.entries
+                .iter()
+                .position(|entry| entry.playing.is_none())
+        };
+
+        let index = match free {
+            Some(index) => index,
+            None => match Source::new(self.clone()) {
+                Ok(source) => {
+                    let mut pool = self.inner.oneshots.borrow_mut();
+                    pool.entries.push(Entry {
+                        handle: source.into_handle(),
+                        generation: 0,
+                        playing: None,
+                    });
+                    pool.entries.len() - 1
+                }
+                Err(err) => self.steal_oneshot(params.priority).ok_or(err)?,
+            },
+        };
+
+        let (handle, generation) = {
+            let mut pool = self.inner.oneshots.borrow_mut();
+            pool.started += 1;
+
+            let started = pool.started;
+            let entry = &mut pool.entries[index];
+            entry.playing = Some(Playing {
+                priority: params.priority,
+                started,
+                buffer: buffer.clone(),
+            });
+
+            (entry.handle, entry.generation)
+        };
+
+        let result = self.with_borrowed_source(handle, |source| {
+            source
+                .set_buffer(Some(&buffer))
+                .and_then(|_| params.apply(source))
+                .and_then(|_| source.play())
+        });
+
+        if let Err(err) = result {
+            self.with_borrowed_source(handle, |source| source.set_buffer(None))?;
+            self.inner.oneshots.borrow_mut().release(index);
+            return Err(err);
+        }
+
+        Ok(OneShot {
+            context: self.clone(),
+            index,
+            generation,
+        })
+    }
+
+    /// Returns the sources of finished one-shots to the pool.
+    pub(crate) fn recycle_oneshots(&self) -> AllenResult<()> {
+        // Dropped once the context isn't locked anymore, as buffers make their own context current.
+        let mut buffers = vec![];
+
+        let _lock = self.make_current();
+        let mut pool = self.inner.oneshots.borrow_mut();
+
+        for index in 0..pool.entries.len() {
+            let entry = &pool.entries[index];
+            if entry.playing.is_none() {
+                continue;
+            }
+
+            let mut state = 0;
+            unsafe { alGetSourcei(entry.handle, AL_SOURCE_STATE, &mut state) };
+            check_al_error()?;
+
+            if state == AL_STOPPED || state == AL_INITIAL {
+                unsafe { alSourcei(entry.handle, AL_BUFFER, 0) };
+                check_al_error()?;
+
+                buffers.extend(pool.release(index));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Cuts off the least important one-shot, and returns its entry.
+    fn steal_oneshot(&self, priority: i32) -> Option<usize> {
+        // Dropped once the context isn't locked anymore, as buffers make their own context current.
+        let _buffer;
+
+        let _lock = self.make_current();
+        let mut pool = self.inner.oneshots.borrow_mut();
+
+        let index = pool
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| Some((index, entry.playing.as_ref()?)))
+            .filter(|(_, playing)| playing.priority <= priority)
+            .min_by_key(|(_, playing)| (playing.priority, playing.started))
+            .map(|(index, _)| index)?;
+
+        let handle = pool.entries[index].handle;
+        unsafe {
+            alSourceStop(handle);
+            alSourcei(handle, AL_BUFFER, 0);
+        }
+        _buffer = pool.release(index);
+
+        Some(index)
+    }
+}
@@ -0,0 +1,385 @@
+use crate::{AllenError, AllenResult, Context, Listener, Source};
+use std::collections::HashMap;
+
+/// How a [`Curve`] fills in the values between its points.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Interpolation {
+    /// Straight lines between points.
+    #[default]
+    Linear,
+    /// A smooth curve through the points. It never overshoots them, so a curve rising from one point to the next keeps rising in between.
+    Spline,
+}
+
+/// Maps a parameter value onto a property value, through a list of `(parameter, property)` points.
+/// Outside of the points, the curve stays at the value of the first or last one.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Curve {
+    pub points: Vec<(f32, f32)>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub interpolation: Interpolation,
+}
+
+impl Curve {
+    pub fn linear(points: Vec<(f32, f32)>) -> Self {
+        Self {
+            points,
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    pub fn spline(points: Vec<(f32, f32)>) -> Self {
+        Self {
+            points,
+            interpolation: Interpolation::Spline,
+        }
+    }
+
+    /// Fails with [`AllenError::InvalidValue`] if the curve has no points, or if they aren't sorted by strictly increasing parameter values.
+    pub fn validate(&self) -> AllenResult<()> {
+        let is_sorted = self.points.windows(2).all(|pair| pair[0].0 < pair[1].0);
+        let is_finite = self
+            .points
+            .iter()
+            .all(|(x, y)| x.is_finite() && y.is_finite());
+
+        if self.points.is_empty() || !is_sorted || !is_finite {
+            return Err(AllenError::InvalidValue);
+        }
+
+        Ok(())
+    }
+
+    /// The property value for a parameter value.
+    /// Fails with [`AllenError::InvalidValue`] if the curve isn't valid (see [`Curve::validate`]), or if the parameter value is NaN.
+    pub fn evaluate(&self, x: f32) -> AllenResult<f32> {
+        self.validate()?;
+        if x.is_nan() {
+            return Err(AllenError::InvalidValue);
+        }
+
+        let points = &self.points;
+        let (first, last) = (points[0], points[points.len() - 1]);
+
+        if x <= first.0 {
+            return Ok(first.1);
+        }
+        if x >= last.0 {
+            return Ok(last.1);
+        }
+
+        // The segment the value falls in.
+        let i = points.partition_point(|point| point.0 <= x) - 1;
+        let ((x0, y0), (x1, y1)) = (points[i], points[i + 1]);
+
+        let h = x1 - x0;
+        let t = (x - x0) / h;
+
+        let y = match self.interpolation {
+            Interpolation::Linear => y0 + (y1 - y0) * t,
+            Interpolation::Spline => {
+                let (m0, m1) = (self.tangent(i) * h, self.tangent(i + 1) * h);
+
+                // Cubic Hermite basis.
+                let t2 = t * t;
+                let t3 = t2 * t;
+                (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+                    + (t3 - 2.0 * t2 + t) * m0
+                    + (-2.0 * t3 + 3.0 * t2) * y1
+                    + (t3 - t2) * m1
+            }
+        };
+
+        Ok(y)
+    }
+
+    fn slope(&self, i: usize) -> f32 {
+        let ((x0, y0), (x1, y1)) = (self.points[i], self.points[i + 1]);
+        (y1 - y0) / (x1 - x0)
+    }
+
+    /// The tangent at a point, limited so that the curve stays monotonic between points (Fritsch-Carlson).
+    fn tangent(&self, i: usize) -> f32 {
+        let last = self.points.len() - 1;
+
+        if i == 0 {
+            return self.slope(0);
+        }
+        if i == last {
+            return self.slope(last - 1);
+        }
+
+        let (before, after) = (self.slope(i - 1), self.slope(i));
+        if before * after <= 0.0 {
+            return 0.0;
+        }
+
+        // Harmonic mean of the neighbouring slopes.
+        2.0 / (1.0 / before + 1.0 / after)
+    }
+}
+
+/// Identifies a binding between a parameter and a property, created with [`Context::bind_source_parameter`] or [`Context::bind_listener_parameter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BindingId(u64);
+
+#[derive(Clone, Copy)]
+enum Target {
+    Source(u32, fn(&Source, f32) -> AllenResult<()>),
+    Listener(fn(&Listener, f32) -> AllenResult<()>),
+}
+
+struct Binding {
+    id: BindingId,
+    parameter: String,
+    target: Target,
+    curve: Curve,
+    /// Whether the property is out of date with the parameter.
+    is_dirty: bool,
+}
+
+/// Game parameters, and the properties they drive.
+#[derive(Default)]
+pub(crate) struct Parameters {
+    next_id: u64,
+    values: HashMap<String, f32>,
+    bindings: Vec<Binding>,
+}
+
+impl Parameters {
+    /// Removes the bindings of a source that's being dropped.
+    pub(crate) fn remove_source(&mut self, handle: u32) {
+        self.bindings.retain(
+            |binding| !matches!(binding.target, Target::Source(other, _) if other == handle),
+        );
+    }
+}
+
+impl Context {
+    /// Sets a game parameter, e.g. "rpm" or "health". The properties bound to it are updated on the next [`Context::update`].
+    ///
+    /// NOTE: Parameters can drive properties of sources and the listener only, as there are no bindings for filters yet.
+    pub fn set_parameter(&self, name: &str, value: f32) -> AllenResult<()> {
+        if !value.is_finite() {
+            return Err(AllenError::InvalidValue);
+        }
+
+        let mut parameters = self.inner.parameters.borrow_mut();
+
+        if parameters.values.get(name) == Some(&value) {
+            return Ok(());
+        }
+        parameters.values.insert(name.to_string(), value);
+
+        for binding in &mut parameters.bindings {
+            if binding.parameter == name {
+                binding.is_dirty = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The value of a game parameter, if it has been set.
+    pub fn parameter(&self, name: &str) -> Option<f32> {
+        self.inner.parameters.borrow().values.get(name).copied()
+    }
+
+    /// Drives a property of a source through one of its setters (e.g. [`Source::set_pitch`]) with a game parameter.
+    /// A property bound to several parameters ends up with the value from whichever binding is applied last.
+    ///
+    /// Fails with [`AllenError::InvalidContext`] if the source belongs to another context.
+    ///
+    /// NOTE: Dropping the source removes its bindings.
+    pub fn bind_source_parameter(
+        &self,
+        name: &str,
+        source: &Source,
+        setter: fn(&Source, f32) -> AllenResult<()>,
+        curve: Curve,
+    ) -> AllenResult<BindingId> {
+        let handle = self.source_handles(&[source])?[0];
+        self.bind_parameter(name, Target::Source(handle, setter), curve)
+    }
+
+    /// Drives a property of the listener through one of its setters (e.g. [`Listener::set_gain`]) with a game parameter.
+    pub fn bind_listener_parameter(
+        &self,
+        name: &str,
+        setter: fn(&Listener, f32) -> AllenResult<()>,
+        curve: Curve,
+    ) -> AllenResult<BindingId> {
+        self.bind_parameter(name, Target::Listener(setter), curve)
+    }
+
+    /// Removes a binding. The property keeps its last value.
+    pub fn unbind_parameter(&self, id: BindingId) {
+        self.inner
+            .parameters
+            .borrow_mut()
+            .bindings
+            .retain(|binding| binding.id != id);
+    }
+
+    fn bind_parameter(&self, name: &str, target: Target, curve: Curve) -> AllenResult<BindingId> {
+        curve.validate()?;
+
+        let mut parameters = self.inner.parameters.borrow_mut();
+
+        let id = BindingId(parameters.next_id);
+        parameters.next_id += 1;
+
+        parameters.bindings.push(Binding {
+            id,
+            parameter: name.to_string(),
+            target,
+            curve,
+            is_dirty: true,
+        });
+
+        Ok(id)
+    }
+
+    /// Pushes the parameters that changed since the last update to the properties bound to them, in a single batch.
+    pub(crate) fn update_parameters(&self) -> AllenResult<()> {
+        let changes = {
+            let mut parameters = self.inner.parameters.borrow_mut();
+            let Parameters {
+                values, bindings, ..
+            } = &mut *parameters;
+
+            bindings
+                .iter_mut()
+                .filter(|binding| binding.is_dirty)
+                .filter_map(|binding| {
+                    let value = *values.get(&binding.parameter)?;
+                    binding.is_dirty = false;
+                    Some(
+                        binding
+                            .curve
+                            .evaluate(value)
+                            .map(|value| (binding.target, value)),
+                    )
+                })
+                .collect::<AllenResult<Vec<_>>>()?
+        };
+
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        self.batch(|context| {
+            let listener = context.listener();
+
+            changes
+                .into_iter()
+                .try_for_each(|(target, value)| match target {
+                    Target::Source(handle, setter) => {
+                        context.with_borrowed_source(handle, |source| setter(source, value))
+                    }
+                    Target::Listener(setter) => setter(&listener, value),
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamps_outside_of_the_points() {
+        for curve in [
+            Curve::linear(vec![(0.0, 1.0), (1.0, 3.0)]),
+            Curve::spline(vec![(0.0, 1.0), (0.5, 2.5), (1.0, 3.0)]),
+        ] {
+            assert_eq!(curve.evaluate(-10.0).unwrap(), 1.0);
+            assert_eq!(curve.evaluate(0.0).unwrap(), 1.0);
+            assert_eq!(curve.evaluate(1.0).unwrap(), 3.0);
+            assert_eq!(curve.evaluate(10.0).unwrap(), 3.0);
+        }
+    }
+
+    #[test]
+    fn passes_through_the_points() {
+        let points = vec![(0.0, 0.0), (1.0, 4.0), (3.0, 5.0), (4.0, 9.0)];
+
+        for curve in [Curve::linear(points.clone()), Curve::spline(points.clone())] {
+            for &(x, y) in &points {
+                assert!((curve.evaluate(x).unwrap() - y).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn interpolates_linearly() {
+        let curve = Curve::linear(vec![(0.0, 0.0), (2.0, 1.0)]);
+        assert!((curve.evaluate(0.5).unwrap() - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn spline_stays_monotonic() {
+        // Steep and flat stretches next to each other are where plain cubic splines overshoot.
+        let curve = Curve::spline(vec![
+            (0.0, 0.0),
+            (1.0, 0.1),
+            (2.0, 10.0),
+            (3.0, 10.0),
+            (4.0, 11.0),
+        ]);
+
+        let mut previous = curve.evaluate(0.0).unwrap();
+        for step in 1..=400 {
+            let value = curve.evaluate(step as f32 / 100.0).unwrap();
+            assert!(
+                value >= previous - 1e-5,
+                "{value} < {previous} at step {step}"
+            );
+            previous = value;
+        }
+
+        // Flat between equal points.
+        for step in 200..=300 {
+            assert!((curve.evaluate(step as f32 / 100.0).unwrap() - 10.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn single_point_is_constant() {
+        for curve in [
+            Curve::linear(vec![(1.0, 2.0)]),
+            Curve::spline(vec![(1.0, 2.0)]),
+        ] {
+            assert!(curve.validate().is_ok());
+            assert_eq!(curve.evaluate(0.0).unwrap(), 2.0);
+            assert_eq!(curve.evaluate(1.0).unwrap(), 2.0);
+            assert_eq!(curve.evaluate(5.0).unwrap(), 2.0);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_curves() {
+        assert!(Curve::linear(vec![]).validate().is_err());
+        assert!(Curve::linear(vec![(1.0, 0.0), (0.0, 1.0)])
+            .validate()
+            .is_err());
+        assert!(Curve::linear(vec![(0.0, 0.0), (0.0, 1.0)])
+            .validate()
+            .is_err());
+        assert!(Curve::linear(vec![(0.0, f32::NAN)]).validate().is_err());
+    }
+
+    #[test]
+    fn fails_to_evaluate_invalid_curves() {
+        assert!(Curve::default().evaluate(0.0).is_err());
+        assert!(Curve::spline(vec![]).evaluate(0.0).is_err());
+        assert!(Curve::linear(vec![(1.0, 0.0), (0.0, 1.0)])
+            .evaluate(0.5)
+            .is_err());
+        assert!(Curve::linear(vec![(0.0, 0.0), (1.0, 1.0)])
+            .evaluate(f32::NAN)
+            .is_err());
+    }
+}
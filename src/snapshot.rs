@@ -0,0 +1,171 @@
+use crate::{tween::fade, AllenError, AllenResult, BusId, Context};
+use std::{collections::BTreeMap, time::Duration};
+
+/// A named set of mixer settings (e.g. "pause menu" or "underwater"), blended in with [`Context::start_snapshot`]
+/// and out with [`Context::stop_snapshot`].
+///
+/// With the ``serde`` feature, snapshots can be loaded from data files. Fade times are given in seconds there, and
+/// every field but the name is optional.
+///
+/// NOTE: Filters and effects (EFX) aren't wrapped by the crate yet, so snapshots only cover bus gains for now.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    pub name: String,
+    /// When several active snapshots set the same bus, higher priorities are blended in last, so they win.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: i32,
+    #[cfg_attr(feature = "serde", serde(default, with = "secs"))]
+    pub fade_in: Duration,
+    #[cfg_attr(feature = "serde", serde(default, with = "secs"))]
+    pub fade_out: Duration,
+    /// Gains by bus name. They scale the buses on top of their own gain (see [`crate::Bus::set_gain`]).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bus_gains: BTreeMap<String, f32>,
+}
+
+#[cfg(feature = "serde")]
+pub(crate) mod secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(value.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Duration::try_from_secs_f64(f64::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+struct ActiveSnapshot {
+    snapshot: Snapshot,
+    /// The bus gains, resolved when the snapshot is started.
+    gains: Vec<(BusId, f32)>,
+    /// How far the snapshot is blended in, from 0 to 1.
+    weight: f32,
+    is_stopping: bool,
+}
+
+/// The active snapshots of a context, ordered by priority.
+#[derive(Default)]
+pub(crate) struct Snapshots {
+    active: Vec<ActiveSnapshot>,
+}
+
+impl Context {
+    /// Starts blending a snapshot in over its fade-in time. Starting a snapshot that's already active replaces its settings,
+    /// and cancels its fade-out if it was being stopped.
+    ///
+    /// Fails with [`AllenError::InvalidName`] if a bus doesn't exist, and with [`AllenError::InvalidValue`] if a gain is negative.
+    pub fn start_snapshot(&self, snapshot: Snapshot) -> AllenResult<()> {
+        let gains = {
+            let tree = self.inner.buses.borrow();
+
+            snapshot
+                .bus_gains
+                .iter()
+                .map(|(name, &gain)| {
+                    if !(gain >= 0.0 && gain.is_finite()) {
+                        return Err(AllenError::InvalidValue);
+                    }
+                    Ok((tree.find(name).ok_or(AllenError::InvalidName)?, gain))
+                })
+                .collect::<AllenResult<Vec<_>>>()?
+        };
+
+        {
+            let mut snapshots = self.inner.snapshots.borrow_mut();
+
+            let weight = match snapshots
+                .active
+                .iter()
+                .position(|active| active.snapshot.name == snapshot.name)
+            {
+                Some(index) => snapshots.active.remove(index).weight,
+                None => 0.0,
+            };
+
+            let index = snapshots
+                .active
+                .iter()
+                .position(|active| active.snapshot.priority > snapshot.priority)
+                .unwrap_or(snapshots.active.len());
+
+            snapshots.active.insert(
+                index,
+                ActiveSnapshot {
+                    snapshot,
+                    gains,
+                    weight,
+                    is_stopping: false,
+                },
+            );
+        }
+
+        self.update_snapshots(Duration::ZERO)
+    }
+
+    /// Starts blending a snapshot out over its fade-out time. Does nothing if the snapshot isn't active.
+    pub fn stop_snapshot(&self, name: &str) -> AllenResult<()> {
+        let mut snapshots = self.inner.snapshots.borrow_mut();
+
+        if let Some(active) = snapshots
+            .active
+            .iter_mut()
+            .find(|active| active.snapshot.name == name)
+        {
+            active.is_stopping = true;
+        }
+
+        drop(snapshots);
+        self.update_snapshots(Duration::ZERO)
+    }
+
+    /// Whether a snapshot has been started, and hasn't finished fading out yet.
+    pub fn is_snapshot_active(&self, name: &str) -> bool {
+        self.inner
+            .snapshots
+            .borrow()
+            .active
+            .iter()
+            .any(|active| active.snapshot.name == name)
+    }
+
+    /// Moves the fades of the active snapshots along by `delta`, and applies the blended bus gains.
+    pub(crate) fn update_snapshots(&self, delta: Duration) -> AllenResult<()> {
+        let mut snapshots = self.inner.snapshots.borrow_mut();
+
+        for active in &mut snapshots.active {
+            let (duration, target) = if active.is_stopping {
+                (active.snapshot.fade_out, 0.0)
+            } else {
+                (active.snapshot.fade_in, 1.0)
+            };
+
+            active.weight = fade(active.weight, target, delta, duration);
+        }
+
+        snapshots
+            .active
+            .retain(|active| !(active.is_stopping && active.weight <= 0.0));
+
+        let _lock = self.make_current();
+        let mut tree = self.inner.buses.borrow_mut();
+
+        // Each snapshot pulls the gain from what the lower priority ones left towards its own value.
+        let mut gains = vec![1.0; tree.len()];
+        for active in &snapshots.active {
+            for &(bus, gain) in &active.gains {
+                gains[bus.0] += (gain - gains[bus.0]) * active.weight;
+            }
+        }
+
+        for (index, gain) in gains.into_iter().enumerate() {
+            tree.set_snapshot_gain(BusId(index), gain);
+        }
+
+        tree.apply()
+    }
+}
@@ -1,6 +1,10 @@
-use crate::{check_al_error, sys::*, AllenResult, Buffer, Context, Float3, PropertiesContainer};
+use crate::{
+    check_al_error, check_al_extension, get_proc_address, sys::*, AllenError, AllenResult, Buffer,
+    Bus, Context, DistanceModel, Float3, Orientation, PropertiesContainer, Resampler,
+};
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::{FromPrimitive, ToPrimitive};
+use std::{collections::HashMap, ffi::CString, mem::ManuallyDrop, ptr, time::Duration};
 
 /// The state of a [`Source`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
@@ -16,6 +20,39 @@ pub enum SourceState {
     Stopped = AL_STOPPED as isize,
 }
 
+/// Identifies a [`Source`] within its context, e.g. in [`crate::Event`]s.
+/// OpenAL reuses the handles of deleted sources, so the id also holds a generation: the id of a deleted source never matches a newer one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceId {
+    handle: u32,
+    generation: u64,
+}
+
+/// The generation of every live source of a context. Shared with OpenAL's event thread, hence the mutex around it.
+#[derive(Default)]
+pub(crate) struct SourceGenerations {
+    next: u64,
+    live: HashMap<u32, u64>,
+}
+
+impl SourceGenerations {
+    fn insert(&mut self, handle: u32) {
+        self.next += 1;
+        self.live.insert(handle, self.next);
+    }
+
+    fn remove(&mut self, handle: u32) {
+        self.live.remove(&handle);
+    }
+
+    /// The id of the live source with the handle, if there is one.
+    pub(crate) fn id(&self, handle: u32) -> Option<SourceId> {
+        self.live
+            .get(&handle)
+            .map(|&generation| SourceId { handle, generation })
+    }
+}
+
 /// A source used to play [`Buffer`]s.
 /// NOTE: Sources are bound to a context.
 pub struct Source {
@@ -118,6 +155,38 @@ impl PropertiesContainer<SourceState> for Source {
     }
 }
 
+impl PropertiesContainer<DistanceModel> for Source {
+    fn get(&self, param: i32) -> AllenResult<DistanceModel> {
+        FromPrimitive::from_i32(PropertiesContainer::<i32>::get(self, param)?)
+            .ok_or(AllenError::InvalidEnum)
+    }
+
+    fn set(&self, param: i32, value: DistanceModel) -> AllenResult<()> {
+        PropertiesContainer::<i32>::set(self, param, ToPrimitive::to_i32(&value).unwrap())
+    }
+}
+
+impl PropertiesContainer<Orientation> for Source {
+    fn get(&self, param: i32) -> AllenResult<Orientation> {
+        let _lock = self.context.make_current();
+
+        let mut value = [0.0; 6];
+        unsafe { alGetSourcefv(self.handle, param, value.as_mut_ptr()) };
+        check_al_error()?;
+
+        Ok(Orientation::from_al(value))
+    }
+
+    fn set(&self, param: i32, value: Orientation) -> AllenResult<()> {
+        let _lock = self.context.make_current();
+
+        unsafe { alSourcefv(self.handle, param, value.to_al().as_ptr()) };
+        check_al_error()?;
+
+        Ok(())
+    }
+}
+
 impl PropertiesContainer<[i32; 3]> for Source {
     fn get(&self, param: i32) -> AllenResult<[i32; 3]> {
         let _lock = self.context.make_current();
@@ -149,10 +218,103 @@ impl PropertiesContainer<[i32; 3]> for Source {
     }
 }
 
+impl PropertiesContainer<[f64; 2]> for Source {
+    fn get(&self, param: i32) -> AllenResult<[f64; 2]> {
+        let _lock = self.context.make_current();
+
+        let function: LPALGETSOURCEDVSOFT = get_proc_address("alGetSourcedvSOFT");
+        let function = function.expect("AL_SOFT_source_latency is present");
+
+        let result = unsafe {
+            let mut value = [0.0, 0.0];
+            function(self.handle, param, value.as_mut_ptr());
+            value
+        };
+
+        check_al_error()?;
+
+        Ok(result)
+    }
+
+    fn set(&self, param: i32, value: [f64; 2]) -> AllenResult<()> {
+        let _lock = self.context.make_current();
+
+        let function: LPALSOURCEDVSOFT = get_proc_address("alSourcedvSOFT");
+        let function = function.expect("AL_SOFT_source_latency is present");
+
+        unsafe { function(self.handle, param, value.as_ptr()) };
+        check_al_error()?;
+
+        Ok(())
+    }
+}
+
+impl PropertiesContainer<[i64; 2]> for Source {
+    fn get(&self, param: i32) -> AllenResult<[i64; 2]> {
+        let _lock = self.context.make_current();
+
+        let function: LPALGETSOURCEI64VSOFT = get_proc_address("alGetSourcei64vSOFT");
+        let function = function.expect("AL_SOFT_source_latency is present");
+
+        let result = unsafe {
+            let mut value = [0, 0];
+            function(self.handle, param, value.as_mut_ptr());
+            value
+        };
+
+        check_al_error()?;
+
+        Ok(result)
+    }
+
+    fn set(&self, param: i32, value: [i64; 2]) -> AllenResult<()> {
+        let _lock = self.context.make_current();
+
+        let function: LPALSOURCEI64VSOFT = get_proc_address("alSourcei64vSOFT");
+        let function = function.expect("AL_SOFT_source_latency is present");
+
+        unsafe { function(self.handle, param, value.as_ptr()) };
+        check_al_error()?;
+
+        Ok(())
+    }
+}
+
+/// A 32.32 fixed-point sample offset, as reported by ``AL_SOFT_source_latency``.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleOffset(pub i64);
+
+impl SampleOffset {
+    /// The whole sample the offset lies in.
+    pub fn samples(&self) -> i64 {
+        self.0 >> 32
+    }
+
+    /// The fractional position between the current and the next sample, from 0 to 1.
+    pub fn fraction(&self) -> f64 {
+        (self.0 & 0xFFFF_FFFF) as f64 / 4294967296.0
+    }
+
+    /// The offset in samples, including the fractional part.
+    pub fn as_f64(&self) -> f64 {
+        self.0 as f64 / 4294967296.0
+    }
+}
+
+/// Converts seconds reported by OpenAL into a [`Duration`], clamping negative values to zero.
+fn secs_to_duration(secs: f64) -> Duration {
+    Duration::from_secs_f64(secs.max(0.0))
+}
+
+/// Converts nanoseconds reported by OpenAL into a [`Duration`], clamping negative values to zero.
+fn nanos_to_duration(nanos: i64) -> Duration {
+    Duration::from_nanos(nanos.max(0) as u64)
+}
+
 #[rustfmt::skip]
 impl Source {
     getter_setter!(pitch, set_pitch, f32, AL_PITCH);
-    getter_setter!(gain, set_gain, f32, AL_GAIN);
     getter_setter!(min_gain, set_min_gain, f32, AL_MIN_GAIN);
     getter_setter!(max_gain, set_max_gain, f32, AL_MAX_GAIN);
     getter_setter!(max_distance, set_max_distance, f32, AL_MAX_DISTANCE);
@@ -181,10 +343,118 @@ impl Source {
     getter!(length_in_samples, i32, AL_SAMPLE_LENGTH_SOFT, "AL_SOFT_source_length");
     getter!(length_in_bytes, f32, AL_BYTE_LENGTH_SOFT, "AL_SOFT_source_length");
 
+    // AL_EXT_BFORMAT; orients the sound field of ambisonic buffers.
+    getter_setter!(orientation, set_orientation, Orientation, AL_ORIENTATION, "AL_EXT_BFORMAT");
+
     // AL_EXT_SOURCE_RADIUS
     getter_setter!(source_radius, set_source_radius, f32, AL_SOURCE_RADIUS, "AL_EXT_SOURCE_RADIUS");
 
+    // Not part of any extension, but supported by OpenAL Soft. Ranges from 0 to 1, and scales the context's doppler factor.
+    getter!(doppler_factor, f32, AL_DOPPLER_FACTOR);
+
+    /// Scales the context's doppler factor (see [`Context::set_doppler_factor`]) for this source.
+    /// Fails with [`AllenError::InvalidValue`] outside of `0.0..=1.0`.
+    pub fn set_doppler_factor(&self, value: f32) -> AllenResult<()> {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(AllenError::InvalidValue);
+        }
+
+        self.set(AL_DOPPLER_FACTOR, value)
+    }
+
+    // AL_SOFT_source_resampler
+    /// The resampler this source uses. See [`Context::resamplers`].
+    pub fn resampler(&self) -> AllenResult<Resampler> {
+        let index = self.get(AL_SOURCE_RESAMPLER_SOFT)?;
+        self.context.resampler(index)
+    }
+
+    /// Sets the resampler this source uses. Resamplers can be found by index or by name through [`Context::resampler`] and [`Context::resampler_by_name`].
+    pub fn set_resampler(&self, resampler: Resampler) -> AllenResult<()> {
+        check_al_extension(&CString::new("AL_SOFT_source_resampler").unwrap())?;
+        self.set(AL_SOURCE_RESAMPLER_SOFT, resampler.index)
+    }
+
+    // AL_SOFT_source_latency
+    /// The playback offset together with the output latency, in double precision.
+    /// The offset plus the latency is roughly when the current offset will be heard.
+    pub fn time_with_latency(&self) -> AllenResult<(Duration, Duration)> {
+        check_al_extension(&CString::new("AL_SOFT_source_latency").unwrap())?;
+        let [offset, latency] = self.get(AL_SEC_OFFSET_LATENCY_SOFT)?;
+        Ok((secs_to_duration(offset), secs_to_duration(latency)))
+    }
+
+    /// The playback offset as a fixed-point sample offset, together with the output latency.
+    pub fn time_in_samples_with_latency(&self) -> AllenResult<(SampleOffset, Duration)> {
+        check_al_extension(&CString::new("AL_SOFT_source_latency").unwrap())?;
+        let [offset, latency] = self.get(AL_SAMPLE_OFFSET_LATENCY_SOFT)?;
+        Ok((SampleOffset(offset), nanos_to_duration(latency)))
+    }
+
+    // ALC_SOFT_device_clock, along with AL_SOFT_source_latency
+    /// The playback offset together with the device clock time it was sampled at. See [`crate::Device::clock`].
+    pub fn time_with_clock(&self) -> AllenResult<(Duration, Duration)> {
+        self.context.device().check_alc_extension(&CString::new("ALC_SOFT_device_clock").unwrap())?;
+        check_al_extension(&CString::new("AL_SOFT_source_latency").unwrap())?;
+        let [offset, clock] = self.get(AL_SEC_OFFSET_CLOCK_SOFT)?;
+        Ok((secs_to_duration(offset), secs_to_duration(clock)))
+    }
+
+    /// The fixed-point sample offset together with the device clock time it was sampled at. See [`crate::Device::clock`].
+    pub fn time_in_samples_with_clock(&self) -> AllenResult<(SampleOffset, Duration)> {
+        self.context.device().check_alc_extension(&CString::new("ALC_SOFT_device_clock").unwrap())?;
+        check_al_extension(&CString::new("AL_SOFT_source_latency").unwrap())?;
+        let [offset, clock] = self.get(AL_SAMPLE_OFFSET_CLOCK_SOFT)?;
+        Ok((SampleOffset(offset), nanos_to_duration(clock)))
+    }
+
+    // AL_EXT_source_distance_model
+    getter!(distance_model, DistanceModel, AL_DISTANCE_MODEL, "AL_EXT_source_distance_model");
+
+    /// Sets the source's own distance model; requires extension ``AL_EXT_source_distance_model``.
+    /// The model is only used while per-source distance models are enabled on the context (see [`Context::set_source_distance_model_enabled`]).
+    pub fn set_distance_model(&self, value: DistanceModel) -> AllenResult<()> {
+        check_al_extension(&CString::new("AL_EXT_source_distance_model").unwrap())?;
+        self.set(AL_DISTANCE_MODEL, value)
+    }
+
+    /// The gain of the source itself, without its bus applied.
+    pub fn gain(&self) -> AllenResult<f32> {
+        match self.context.source_gain(self.handle) {
+            Some(gain) => Ok(gain),
+            None => self.get(AL_GAIN),
+        }
+    }
+
+    /// Sets the gain of the source. If the source is on a bus, the bus gain is applied on top of it.
+    pub fn set_gain(&self, value: f32) -> AllenResult<()> {
+        let _lock = self.context.make_current();
+
+        if self.context.set_source_gain(self.handle, value)? {
+            Ok(())
+        } else {
+            self.set(AL_GAIN, value)
+        }
+    }
+
+    /// The bus the source is on, if any.
+    pub fn bus(&self) -> Option<Bus> {
+        self.context.source_bus(self.handle)
+    }
+
+    /// Puts the source on a bus, or takes it off its bus.
+    pub fn set_bus(&self, bus: Option<&Bus>) -> AllenResult<()> {
+        let _lock = self.context.make_current();
+        self.context.set_source_bus(self.handle, bus)
+    }
+
+    /// Attaches a buffer, or detaches the current one.
+    /// Fails with [`AllenError::InvalidDevice`] if the buffer belongs to another device than the source's context.
     pub fn set_buffer(&self, buffer: Option<&Buffer>) -> AllenResult<()> {
+        if let Some(buffer) = buffer {
+            self.check_device(buffer)?;
+        }
+
         self.set(
             AL_BUFFER,
             match buffer {
@@ -208,19 +478,90 @@ impl Source {
 
         check_al_error()?;
 
+        context
+            .inner
+            .source_generations
+            .lock()
+            .unwrap()
+            .insert(handle);
+
         Ok(Self { handle, context })
     }
 
+    pub(crate) fn handle(&self) -> u32 {
+        self.handle
+    }
+
+    /// Gives up ownership of the handle without deleting it.
+    pub(crate) fn into_handle(self) -> u32 {
+        let this = ManuallyDrop::new(self);
+        // The context still needs to be released.
+        drop(unsafe { ptr::read(&this.context) });
+        this.handle
+    }
+
+    /// An identifier for the source, e.g. to match it against [`crate::Event`]s.
+    pub fn id(&self) -> SourceId {
+        let generations = self.context.inner.source_generations.lock().unwrap();
+
+        // Every source is registered when it's created.
+        generations.id(self.handle).unwrap_or(SourceId {
+            handle: self.handle,
+            generation: 0,
+        })
+    }
+
+    pub(crate) fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Buffer names are only unique within a device, so a buffer of another device could stand for an unrelated one here.
+    fn check_device(&self, buffer: &Buffer) -> AllenResult<()> {
+        if buffer.device().is_same(self.context.device()) {
+            Ok(())
+        } else {
+            Err(AllenError::InvalidDevice)
+        }
+    }
+
     pub fn play(&self) -> AllenResult<()> {
         let _lock = self.context.make_current();
 
-        unsafe {
-            alDistanceModel(ToPrimitive::to_i32(&self.context.get_distance_model()).unwrap());
-            alSourcePlay(self.handle);
-        }
+        unsafe { alSourcePlay(self.handle) };
+        check_al_error()
+    }
+
+    /// Starts playing the source once the device clock reaches `start_time` (see [`crate::Device::clock`]).
+    /// A time already in the past starts the source as soon as possible. Requires extension ``AL_SOFT_source_start_delay``.
+    ///
+    /// The distance model is not part of the scheduled start: changes to it apply immediately, even while the source is waiting to play.
+    pub fn play_at(&self, start_time: Duration) -> AllenResult<()> {
+        let _lock = self.context.make_current();
+        check_al_extension(&CString::new("AL_SOFT_source_start_delay").unwrap())?;
+
+        let function: LPALSOURCEPLAYATTIMESOFT = get_proc_address("alSourcePlayAtTimeSOFT");
+        let function = function.expect("AL_SOFT_source_start_delay is present");
+
+        unsafe { function(self.handle, start_time.as_nanos() as i64) };
         check_al_error()
     }
 
+    /// Plays a buffer from the start, then loops from `loop_start` to its end until looping is turned off (see [`Source::set_looping`]),
+    /// e.g. for music with an intro. Requires extension ``AL_SOFT_loop_points``.
+    ///
+    /// NOTE: This sets the loop points of the buffer, so it mustn't be attached to any other source. See [`Buffer::set_loop_range`].
+    pub fn play_intro_loop(&self, buffer: &Buffer, loop_start: Duration) -> AllenResult<()> {
+        // The buffer may still be attached to this source from an earlier call.
+        self.stop()?;
+        self.set_buffer(None)?;
+
+        buffer.set_loop_times(loop_start..buffer.duration()?)?;
+
+        self.set_buffer(Some(buffer))?;
+        self.set_looping(true)?;
+        self.play()
+    }
+
     pub fn pause(&self) -> AllenResult<()> {
         let _lock = self.context.make_current();
 
@@ -242,7 +583,12 @@ impl Source {
         check_al_error()
     }
 
+    /// Fails with [`AllenError::InvalidDevice`] if a buffer belongs to another device than the source's context.
     pub fn queue_buffers(&self, buffers: &[&Buffer]) -> AllenResult<()> {
+        for buffer in buffers {
+            self.check_device(buffer)?;
+        }
+
         let _lock = self.context.make_current();
 
         let buffers = buffers
@@ -278,8 +624,53 @@ impl Source {
     }
 }
 
+/// A source that's only known by its handle, borrowed for the duration of [`Context::with_borrowed_source`].
+/// The handle is never deleted, even if the closure panics.
+struct BorrowedSource(ManuallyDrop<Source>);
+
+impl Drop for BorrowedSource {
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::take(&mut self.0) }.into_handle();
+    }
+}
+
+impl Context {
+    /// Runs `f` on a source of this context, e.g. one held by the one-shot pool or targeted by a tween.
+    pub(crate) fn with_borrowed_source<T>(&self, handle: u32, f: impl FnOnce(&Source) -> T) -> T {
+        let source = BorrowedSource(ManuallyDrop::new(Source {
+            handle,
+            context: self.clone(),
+        }));
+
+        f(&source.0)
+    }
+}
+
 impl Drop for Source {
     fn drop(&mut self) {
+        self.context
+            .inner
+            .buses
+            .borrow_mut()
+            .remove_member(self.handle);
+        self.context
+            .inner
+            .tweens
+            .borrow_mut()
+            .remove_source(self.handle);
+        self.context
+            .inner
+            .parameters
+            .borrow_mut()
+            .remove_source(self.handle);
+        self.context
+            .inner
+            .source_generations
+            .lock()
+            .unwrap()
+            .remove(self.handle);
+
+        let _lock = self.context.make_current();
         unsafe { alDeleteSources(1, &self.handle) }
         if let Err(err) = check_al_error() {
             println!("WARNING: Source drop failed! {}", err);
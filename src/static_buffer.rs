@@ -0,0 +1,168 @@
+use crate::{
+    check_al_error, sys::*, AllenResult, Buffer, Channels, Context, ContextInner, Device, Sample,
+    Source,
+};
+use std::{
+    any::Any,
+    cell::RefCell,
+    marker::PhantomData,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+/// A buffer that plays sample memory in place instead of copying it, e.g. straight out of a memory-mapped file.
+/// Requires extension ``AL_EXT_STATIC_BUFFER``.
+///
+/// The memory is borrowed for `'a`, or kept alive through an [`Arc`]. Sources are attached with [`Source::set_static_buffer`],
+/// and dropping the buffer stops and detaches every source still playing it before the memory can be released.
+/// As leaking the buffer would skip that, borrowed memory is only safe to use through [`Device::with_static_buffer`].
+pub struct StaticBuffer<'a> {
+    buffer: Buffer,
+    /// Sources the buffer was attached to, along with their contexts. Some of them may have moved on to other buffers since.
+    sources: RefCell<Vec<(Weak<ContextInner>, u32)>>,
+    /// Keeps shared memory alive. Dropped after `buffer`.
+    _owner: Option<Box<dyn Any>>,
+    _data: PhantomData<&'a [u8]>,
+}
+
+impl StaticBuffer<'_> {
+    /// # Safety
+    /// The data has to outlive the buffer: either it's borrowed for the buffer's lifetime, or it's kept alive by `owner`.
+    pub(crate) unsafe fn new<T: Sample>(
+        buffer: Buffer,
+        data: &[T],
+        channels: Channels,
+        sample_rate: i32,
+        owner: Option<Box<dyn Any>>,
+    ) -> AllenResult<Self> {
+        // Sources playing the buffer are detached before it's dropped, along with its owner.
+        buffer.data_static(T::buffer_data(data), channels, sample_rate)?;
+
+        Ok(Self {
+            buffer,
+            sources: RefCell::new(vec![]),
+            _owner: owner,
+            _data: PhantomData,
+        })
+    }
+
+    /// The device the buffer belongs to.
+    pub fn device(&self) -> &Device {
+        self.buffer.device()
+    }
+
+    pub fn frequency(&self) -> AllenResult<i32> {
+        self.buffer.frequency()
+    }
+
+    pub fn size(&self) -> AllenResult<i32> {
+        self.buffer.size()
+    }
+
+    pub fn bits(&self) -> AllenResult<i32> {
+        self.buffer.bits()
+    }
+
+    pub fn channels(&self) -> AllenResult<Channels> {
+        self.buffer.channels()
+    }
+
+    /// See [`Buffer::length_in_samples`].
+    pub fn length_in_samples(&self) -> AllenResult<i32> {
+        self.buffer.length_in_samples()
+    }
+
+    /// See [`Buffer::duration`].
+    pub fn duration(&self) -> AllenResult<Duration> {
+        self.buffer.duration()
+    }
+}
+
+impl Drop for StaticBuffer<'_> {
+    fn drop(&mut self) {
+        for (context, source) in self.sources.get_mut().iter() {
+            // Sources don't outlive their context.
+            let Some(inner) = context.upgrade() else {
+                continue;
+            };
+            let context = Context { inner };
+            let _lock = context.make_current();
+
+            let mut buffer = 0;
+            unsafe {
+                if alIsSource(*source) == AL_FALSE as ALboolean {
+                    continue;
+                }
+                alGetSourcei(*source, AL_BUFFER, &mut buffer);
+            }
+
+            if buffer as u32 == self.buffer.handle() {
+                unsafe {
+                    alSourceStop(*source);
+                    alSourcei(*source, AL_BUFFER, 0);
+                }
+            }
+
+            if let Err(err) = check_al_error() {
+                println!("WARNING: StaticBuffer detach failed! {}", err);
+            }
+        }
+    }
+}
+
+impl Source {
+    /// Attaches a static buffer, like [`Source::set_buffer`]. The source is detached when the buffer is dropped.
+    pub fn set_static_buffer(&self, buffer: &StaticBuffer) -> AllenResult<()> {
+        self.set_buffer(Some(&buffer.buffer))?;
+
+        let context = Arc::downgrade(&self.context().inner);
+
+        let mut sources = buffer.sources.borrow_mut();
+        if !sources
+            .iter()
+            .any(|(other, handle)| other.ptr_eq(&context) && *handle == self.handle())
+        {
+            sources.push((context, self.handle()));
+        }
+
+        Ok(())
+    }
+}
+
+impl Context {
+    /// Runs `f` with a buffer playing `data` in place on the context's device. See [`Device::with_static_buffer`].
+    pub fn with_static_buffer<T: Sample, R>(
+        &self,
+        data: &[T],
+        channels: Channels,
+        sample_rate: i32,
+        f: impl FnOnce(&StaticBuffer) -> R,
+    ) -> AllenResult<R> {
+        self.device()
+            .with_static_buffer(data, channels, sample_rate, f)
+    }
+
+    /// Creates a buffer playing `data` in place on the context's device.
+    ///
+    /// # Safety
+    /// See [`Device::new_static_buffer`].
+    pub unsafe fn new_static_buffer<'a, T: Sample>(
+        &self,
+        data: &'a [T],
+        channels: Channels,
+        sample_rate: i32,
+    ) -> AllenResult<StaticBuffer<'a>> {
+        self.device().new_static_buffer(data, channels, sample_rate)
+    }
+
+    /// Creates a buffer playing shared `data` in place on the context's device. See [`Device::new_shared_static_buffer`].
+    pub fn new_shared_static_buffer<T: Sample>(
+        &self,
+        data: Arc<[T]>,
+        channels: Channels,
+        sample_rate: i32,
+    ) -> AllenResult<StaticBuffer<'static>> {
+        self.device()
+            .new_shared_static_buffer(data, channels, sample_rate)
+    }
+}
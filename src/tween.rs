@@ -0,0 +1,430 @@
+use crate::{AllenResult, Context, Float3, Listener, Orientation, Source};
+use std::{f32::consts::PI, rc::Rc, time::Duration};
+
+/// Easing curves for [`Tween`]s.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+}
+
+impl Easing {
+    /// Maps linear progress (0 to 1) onto the curve.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => -((t * PI).cos() - 1.0) / 2.0,
+        }
+    }
+}
+
+/// Moves a value between 0 and 1 towards `target`, covering the whole range in `duration`.
+pub(crate) fn fade(value: f32, target: f32, delta: Duration, duration: Duration) -> f32 {
+    if duration.is_zero() {
+        return target;
+    }
+
+    let step = delta.as_secs_f32() / duration.as_secs_f32();
+    if target > value {
+        (value + step).min(target)
+    } else {
+        (value - step).max(target)
+    }
+}
+
+/// Values that can be tweened.
+pub trait Tweenable: Copy + 'static {
+    fn lerp(from: Self, to: Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        from + (to - from) * t
+    }
+}
+
+impl Tweenable for [f32; 3] {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        [0, 1, 2].map(|i| f32::lerp(from[i], to[i], t))
+    }
+}
+
+impl Tweenable for Orientation {
+    fn lerp(from: Self, to: Self, t: f32) -> Self {
+        let (from_up, from_at, to_up, to_at) = (from.up, from.at, to.up, to.at);
+
+        Orientation {
+            up: Tweenable::lerp(from_up, to_up, t),
+            at: Tweenable::lerp(from_at, to_at, t),
+        }
+    }
+}
+
+/// The properties tweens can move. A tween cancels any other tween running on the same property of the same target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TweenProperty {
+    Gain,
+    Pitch,
+    MinGain,
+    MaxGain,
+    MaxDistance,
+    RolloffFactor,
+    ReferenceDistance,
+    ConeOuterGain,
+    ConeInnerAngle,
+    ConeOuterAngle,
+    Position,
+    Velocity,
+    Direction,
+    Orientation,
+}
+
+/// A property of a [`Source`] that can be tweened with [`Context::tween_source`], e.g. [`SourceProperty::GAIN`].
+#[derive(Clone, Copy)]
+pub struct SourceProperty<T> {
+    property: TweenProperty,
+    setter: fn(&Source, T) -> AllenResult<()>,
+}
+
+#[rustfmt::skip]
+impl SourceProperty<f32> {
+    pub const GAIN: Self = Self { property: TweenProperty::Gain, setter: Source::set_gain };
+    pub const PITCH: Self = Self { property: TweenProperty::Pitch, setter: Source::set_pitch };
+    pub const MIN_GAIN: Self = Self { property: TweenProperty::MinGain, setter: Source::set_min_gain };
+    pub const MAX_GAIN: Self = Self { property: TweenProperty::MaxGain, setter: Source::set_max_gain };
+    pub const MAX_DISTANCE: Self = Self { property: TweenProperty::MaxDistance, setter: Source::set_max_distance };
+    pub const ROLLOFF_FACTOR: Self = Self { property: TweenProperty::RolloffFactor, setter: Source::set_rolloff_factor };
+    pub const REFERENCE_DISTANCE: Self = Self { property: TweenProperty::ReferenceDistance, setter: Source::set_reference_distance };
+    pub const CONE_OUTER_GAIN: Self = Self { property: TweenProperty::ConeOuterGain, setter: Source::set_cone_outer_gain };
+    pub const CONE_INNER_ANGLE: Self = Self { property: TweenProperty::ConeInnerAngle, setter: Source::set_cone_inner_angle };
+    pub const CONE_OUTER_ANGLE: Self = Self { property: TweenProperty::ConeOuterAngle, setter: Source::set_cone_outer_angle };
+}
+
+#[rustfmt::skip]
+impl SourceProperty<Float3> {
+    pub const POSITION: Self = Self { property: TweenProperty::Position, setter: Source::set_position };
+    pub const VELOCITY: Self = Self { property: TweenProperty::Velocity, setter: Source::set_velocity };
+    pub const DIRECTION: Self = Self { property: TweenProperty::Direction, setter: Source::set_direction };
+}
+
+#[rustfmt::skip]
+impl SourceProperty<Orientation> {
+    /// Requires extension ``AL_EXT_BFORMAT``.
+    pub const ORIENTATION: Self = Self { property: TweenProperty::Orientation, setter: Source::set_orientation };
+}
+
+/// A property of the [`Listener`] that can be tweened with [`Context::tween_listener`], e.g. [`ListenerProperty::GAIN`].
+#[derive(Clone, Copy)]
+pub struct ListenerProperty<T> {
+    property: TweenProperty,
+    setter: fn(&Listener, T) -> AllenResult<()>,
+}
+
+#[rustfmt::skip]
+impl ListenerProperty<f32> {
+    pub const GAIN: Self = Self { property: TweenProperty::Gain, setter: Listener::set_gain };
+}
+
+#[rustfmt::skip]
+impl ListenerProperty<Float3> {
+    pub const POSITION: Self = Self { property: TweenProperty::Position, setter: Listener::set_position };
+    pub const VELOCITY: Self = Self { property: TweenProperty::Velocity, setter: Listener::set_velocity };
+}
+
+#[rustfmt::skip]
+impl ListenerProperty<Orientation> {
+    pub const ORIENTATION: Self = Self { property: TweenProperty::Orientation, setter: Listener::set_orientation };
+}
+
+type Callback<S> = Box<dyn FnOnce(&S) -> AllenResult<()>>;
+type Step = Box<dyn FnMut(&Context, f32) -> AllenResult<()>>;
+/// Runs a closure on the target of a tween.
+type Runner<S> = Rc<dyn Fn(&Context, &mut dyn FnMut(&S) -> AllenResult<()>) -> AllenResult<()>>;
+
+/// A transition of a property from one value to another, created with [`Context::tween_source`] or [`Context::tween_listener`].
+#[must_use = "tweens do nothing until they're started"]
+pub struct Tween<'a, T, S> {
+    context: &'a Context,
+    target: Target,
+    property: TweenProperty,
+    setter: fn(&S, T) -> AllenResult<()>,
+    run: Runner<S>,
+    from: T,
+    to: T,
+    duration: Duration,
+    easing: Easing,
+    on_complete: Option<Callback<S>>,
+}
+
+impl<T: Tweenable, S: 'static> Tween<'_, T, S> {
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Called once the tween reaches its end, e.g. to stop a source after fading it out. Not called if the tween is cancelled.
+    pub fn on_complete(mut self, f: impl FnOnce(&S) -> AllenResult<()> + 'static) -> Self {
+        self.on_complete = Some(Box::new(f));
+        self
+    }
+
+    /// Applies the starting value, and lets [`Context::update`] take it from there.
+    /// A tween already running on the same property of the same target is cancelled.
+    pub fn start(self) -> AllenResult<TweenId> {
+        let Tween {
+            context,
+            target,
+            property,
+            setter,
+            run,
+            from,
+            to,
+            ..
+        } = self;
+
+        run(context, &mut |target| setter(target, from))?;
+
+        let on_complete = self.on_complete.map(|f| {
+            let run = run.clone();
+            let mut f = Some(f);
+
+            Box::new(move |context: &Context| {
+                run(context, &mut |target| match f.take() {
+                    Some(f) => f(target),
+                    None => Ok(()),
+                })
+            }) as Callback<Context>
+        });
+
+        Ok(context.start_tween(ActiveTween {
+            id: TweenId(0),
+            target,
+            property,
+            elapsed: Duration::ZERO,
+            duration: self.duration,
+            easing: self.easing,
+            step: Box::new(move |context, t| {
+                run(context, &mut |target| setter(target, T::lerp(from, to, t)))
+            }),
+            on_complete,
+        }))
+    }
+}
+
+/// Identifies a tween started on a [`Context`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TweenId(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Source(u32),
+    Listener,
+}
+
+struct ActiveTween {
+    id: TweenId,
+    target: Target,
+    property: TweenProperty,
+    elapsed: Duration,
+    duration: Duration,
+    easing: Easing,
+    /// Applies the value at the given (eased) progress.
+    step: Step,
+    on_complete: Option<Callback<Context>>,
+}
+
+#[derive(Default)]
+pub(crate) struct Tweens {
+    next_id: u64,
+    active: Vec<ActiveTween>,
+    /// Sources dropped while tweens were being updated.
+    dropped_sources: Vec<u32>,
+}
+
+impl Tweens {
+    /// Cancels the tweens of a source that's being dropped.
+    pub(crate) fn remove_source(&mut self, handle: u32) {
+        self.active
+            .retain(|tween| tween.target != Target::Source(handle));
+        self.dropped_sources.push(handle);
+    }
+}
+
+impl Context {
+    /// Creates a tween moving a property of a source from one value to another, e.g. [`SourceProperty::GAIN`].
+    /// Tweens are advanced by [`Context::update`].
+    /// Fails with [`crate::AllenError::InvalidContext`] if the source belongs to another context.
+    ///
+    /// NOTE: Dropping the source cancels its tweens.
+    pub fn tween_source<T: Tweenable>(
+        &self,
+        source: &Source,
+        property: SourceProperty<T>,
+        from: T,
+        to: T,
+        duration: Duration,
+    ) -> AllenResult<Tween<'_, T, Source>> {
+        let handle = self.source_handles(&[source])?[0];
+
+        Ok(Tween {
+            context: self,
+            target: Target::Source(handle),
+            property: property.property,
+            setter: property.setter,
+            run: Rc::new(move |context, f| context.with_borrowed_source(handle, f)),
+            from,
+            to,
+            duration,
+            easing: Easing::default(),
+            on_complete: None,
+        })
+    }
+
+    /// Creates a tween moving a property of the listener from one value to another, e.g. [`ListenerProperty::GAIN`].
+    /// Tweens are advanced by [`Context::update`].
+    pub fn tween_listener<T: Tweenable>(
+        &self,
+        property: ListenerProperty<T>,
+        from: T,
+        to: T,
+        duration: Duration,
+    ) -> Tween<'_, T, Listener> {
+        Tween {
+            context: self,
+            target: Target::Listener,
+            property: property.property,
+            setter: property.setter,
+            run: Rc::new(|context, f| f(&context.listener())),
+            from,
+            to,
+            duration,
+            easing: Easing::default(),
+            on_complete: None,
+        }
+    }
+
+    /// Stops a tween where it is, without calling its completion callback.
+    pub fn cancel_tween(&self, id: TweenId) {
+        self.inner
+            .tweens
+            .borrow_mut()
+            .active
+            .retain(|tween| tween.id != id);
+    }
+
+    /// Whether the tween is still running.
+    pub fn is_tween_active(&self, id: TweenId) -> bool {
+        self.inner
+            .tweens
+            .borrow()
+            .active
+            .iter()
+            .any(|tween| tween.id == id)
+    }
+
+    fn start_tween(&self, mut tween: ActiveTween) -> TweenId {
+        let mut tweens = self.inner.tweens.borrow_mut();
+
+        tween.id = TweenId(tweens.next_id);
+        tweens.next_id += 1;
+
+        tweens
+            .active
+            .retain(|other| (other.target, other.property) != (tween.target, tween.property));
+
+        let id = tween.id;
+        tweens.active.push(tween);
+        id
+    }
+
+    /// Advances every tween by `delta`, calling completion callbacks of the ones that finish.
+    pub(crate) fn update_tweens(&self, delta: Duration) -> AllenResult<()> {
+        // Taken out, so that callbacks can start new tweens.
+        let mut active = {
+            let mut tweens = self.inner.tweens.borrow_mut();
+            tweens.dropped_sources.clear();
+            std::mem::take(&mut tweens.active)
+        };
+
+        let mut result = Ok(());
+        let mut finished = vec![];
+
+        for tween in &mut active {
+            tween.elapsed = (tween.elapsed + delta).min(tween.duration);
+
+            let t = if tween.duration.is_zero() {
+                1.0
+            } else {
+                tween.elapsed.as_secs_f32() / tween.duration.as_secs_f32()
+            };
+
+            result = result.and((tween.step)(self, tween.easing.apply(t)));
+
+            if tween.elapsed >= tween.duration {
+                finished.push(tween.id);
+            }
+        }
+
+        let (done, mut remaining): (Vec<_>, Vec<_>) = active
+            .into_iter()
+            .partition(|tween| finished.contains(&tween.id));
+
+        for tween in done {
+            if let Some(on_complete) = tween.on_complete {
+                result = result.and(on_complete(self));
+            }
+        }
+
+        let mut tweens = self.inner.tweens.borrow_mut();
+        let dropped_sources = std::mem::take(&mut tweens.dropped_sources);
+        remaining.retain(|tween| match tween.target {
+            Target::Source(handle) => !dropped_sources.contains(&handle),
+            Target::Listener => true,
+        });
+
+        // Tweens started by callbacks replace the ones they collide with.
+        let started = std::mem::take(&mut tweens.active);
+        remaining.retain(|tween| {
+            !started
+                .iter()
+                .any(|other| (other.target, other.property) == (tween.target, tween.property))
+        });
+        remaining.extend(started);
+        tweens.active = remaining;
+
+        result
+    }
+}
@@ -0,0 +1,448 @@
+use crate::{
+    sys::*, AllenError, AllenResult, Buffer, BusId, Context, Float3, PropertiesContainer, Source,
+    SourceState,
+};
+use std::{cmp::Ordering, rc::Rc, time::Duration};
+
+/// Parameters of a voice played through a [`VoiceManager`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoiceParams {
+    /// Voices with a higher priority keep their source over voices with a lower one, regardless of how audible they are.
+    pub priority: i32,
+    pub gain: f32,
+    pub pitch: f32,
+    pub position: Float3,
+    pub is_relative: bool,
+    pub is_looping: bool,
+    pub reference_distance: f32,
+    pub rolloff_factor: f32,
+    pub max_distance: f32,
+    /// The bus the voice plays on. See [`crate::Bus`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub bus: Option<BusId>,
+}
+
+impl Default for VoiceParams {
+    fn default() -> Self {
+        Self {
+            priority: 0,
+            gain: 1.0,
+            pitch: 1.0,
+            position: [0.0, 0.0, 0.0],
+            is_relative: false,
+            is_looping: false,
+            reference_distance: 1.0,
+            rolloff_factor: 1.0,
+            max_distance: f32::MAX,
+            bus: None,
+        }
+    }
+}
+
+impl VoiceParams {
+    /// Fails with [`AllenError::InvalidName`] if the bus doesn't exist in the context.
+    pub(crate) fn validate(&self, context: &Context) -> AllenResult<()> {
+        match self.bus {
+            Some(id) if context.bus_by_id(id).is_none() => Err(AllenError::InvalidName),
+            _ => Ok(()),
+        }
+    }
+
+    /// Applies the parameters to a source.
+    pub(crate) fn apply(&self, source: &Source) -> AllenResult<()> {
+        let bus = match self.bus {
+            Some(id) => Some(
+                source
+                    .context()
+                    .bus_by_id(id)
+                    .ok_or(AllenError::InvalidName)?,
+            ),
+            None => None,
+        };
+
+        // The bus goes first, so that the gain below is taken as the source's own gain.
+        source.set_bus(bus.as_ref())?;
+        source.set_gain(self.gain)?;
+        source.set_pitch(self.pitch)?;
+        source.set_position(self.position)?;
+        source.set_relative(self.is_relative)?;
+        source.set_looping(self.is_looping)?;
+        source.set_reference_distance(self.reference_distance)?;
+        source.set_rolloff_factor(self.rolloff_factor)?;
+        source.set_max_distance(self.max_distance)
+    }
+
+    /// A rough estimate of how loud the voice is at the listener, following the inverse clamped distance model.
+    fn audibility(&self, listener_position: Float3) -> f32 {
+        let origin = if self.is_relative {
+            [0.0, 0.0, 0.0]
+        } else {
+            listener_position
+        };
+
+        let distance = (0..3)
+            .map(|i| (self.position[i] - origin[i]).powi(2))
+            .sum::<f32>()
+            .sqrt()
+            .clamp(
+                self.reference_distance,
+                self.max_distance.max(self.reference_distance),
+            );
+
+        let attenuation = self.reference_distance
+            / (self.reference_distance
+                + self.rolloff_factor * (distance - self.reference_distance));
+
+        if attenuation.is_finite() {
+            self.gain * attenuation
+        } else {
+            self.gain
+        }
+    }
+}
+
+/// Identifies a voice of a [`VoiceManager`]. Stays valid until the voice finishes or is stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceId {
+    index: u32,
+    generation: u32,
+}
+
+struct Voice {
+    buffer: Rc<Buffer>,
+    length: Duration,
+    params: VoiceParams,
+    /// Where playback is at. Only kept up to date while the voice is virtual.
+    offset: Duration,
+    source: Option<usize>,
+    is_paused: bool,
+}
+
+struct Slot {
+    generation: u32,
+    voice: Option<Voice>,
+}
+
+/// Plays any amount of voices on a limited amount of sources.
+///
+/// When there are more voices than sources, the voices with the lowest priority (or the least audible ones, when priorities are equal) become virtual:
+/// they have no source, but their playback position keeps advancing through [`VoiceManager::update`].
+/// Whenever a source frees up, the most important virtual voice gets it back and resumes where it would've been.
+/// A voice that fails to start playing on a source is stopped.
+///
+/// Voices keep their [`Buffer`] alive until they finish.
+pub struct VoiceManager {
+    context: Context,
+    sources: Vec<Source>,
+    owners: Vec<Option<VoiceId>>,
+    slots: Vec<Slot>,
+}
+
+impl VoiceManager {
+    pub(crate) fn new(context: Context, max_sources: usize) -> AllenResult<Self> {
+        let mut sources = Vec::with_capacity(max_sources);
+
+        while sources.len() < max_sources {
+            match context.new_source() {
+                Ok(source) => sources.push(source),
+                // The implementation ran out of sources; make do with what we got.
+                Err(_) if !sources.is_empty() => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Self {
+            owners: vec![None; sources.len()],
+            context,
+            sources,
+            slots: vec![],
+        })
+    }
+
+    /// The amount of real sources voices are played on.
+    pub fn source_count(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Starts playing a buffer as a new voice. The voice is virtual from the start if all sources are taken by more important voices.
+    /// Fails with [`AllenError::InvalidDevice`] if the buffer belongs to another device than the manager's context,
+    /// and with [`AllenError::InvalidName`] if the bus doesn't exist in the context.
+    pub fn play(&mut self, buffer: Rc<Buffer>, params: VoiceParams) -> AllenResult<VoiceId> {
+        if !buffer.device().is_same(self.context.device()) {
+            return Err(AllenError::InvalidDevice);
+        }
+        params.validate(&self.context)?;
+
+        let voice = Voice {
+            length: buffer.duration()?,
+            buffer,
+            params,
+            offset: Duration::ZERO,
+            source: None,
+            is_paused: false,
+        };
+
+        let id = match self.slots.iter().position(|slot| slot.voice.is_none()) {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.voice = Some(voice);
+                VoiceId {
+                    index: index as u32,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    voice: Some(voice),
+                });
+                VoiceId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        self.rebalance()?;
+
+        Ok(id)
+    }
+
+    /// Stops a voice. Its id becomes invalid.
+    pub fn stop(&mut self, id: VoiceId) -> AllenResult<()> {
+        self.voice(id)?;
+        self.finish(id.index as usize)
+    }
+
+    /// Pauses a voice. Paused voices give up their source, and resume where they left off.
+    pub fn pause(&mut self, id: VoiceId) -> AllenResult<()> {
+        self.voice_mut(id)?.is_paused = true;
+        self.rebalance()
+    }
+
+    pub fn resume(&mut self, id: VoiceId) -> AllenResult<()> {
+        self.voice_mut(id)?.is_paused = false;
+        self.rebalance()
+    }
+
+    /// Whether the voice is still around, i.e. it has neither finished nor been stopped.
+    pub fn is_active(&self, id: VoiceId) -> bool {
+        self.voice(id).is_ok()
+    }
+
+    /// Whether the voice is currently playing without a source.
+    pub fn is_virtual(&self, id: VoiceId) -> AllenResult<bool> {
+        Ok(self.voice(id)?.source.is_none())
+    }
+
+    pub fn is_paused(&self, id: VoiceId) -> AllenResult<bool> {
+        Ok(self.voice(id)?.is_paused)
+    }
+
+    /// The playback position of the voice.
+    pub fn offset(&self, id: VoiceId) -> AllenResult<Duration> {
+        let voice = self.voice(id)?;
+
+        match voice.source {
+            Some(source) => Ok(Duration::from_secs_f32(
+                self.sources[source].time_in_secs()?.max(0.0),
+            )),
+            None => Ok(voice.offset),
+        }
+    }
+
+    pub fn params(&self, id: VoiceId) -> AllenResult<VoiceParams> {
+        Ok(self.voice(id)?.params)
+    }
+
+    /// Changes the parameters of a voice. Priority and audibility changes are taken into account on the next [`VoiceManager::update`].
+    pub fn set_params(&mut self, id: VoiceId, params: VoiceParams) -> AllenResult<()> {
+        params.validate(&self.context)?;
+
+        let voice = self.voice_mut(id)?;
+        voice.params = params;
+
+        if let Some(source) = voice.source {
+            params.apply(&self.sources[source])?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_position(&mut self, id: VoiceId, position: Float3) -> AllenResult<()> {
+        let voice = self.voice_mut(id)?;
+        voice.params.position = position;
+
+        if let Some(source) = voice.source {
+            self.sources[source].set_position(position)?;
+        }
+
+        Ok(())
+    }
+
+    /// Advances virtual voices by `delta`, retires voices that finished playing, and hands sources to the most important voices.
+    pub fn update(&mut self, delta: Duration) -> AllenResult<()> {
+        for index in 0..self.slots.len() {
+            let Some(voice) = &mut self.slots[index].voice else {
+                continue;
+            };
+
+            let is_finished = match voice.source {
+                Some(source) => self.sources[source].state()? == SourceState::Stopped,
+                None if voice.is_paused => false,
+                None => {
+                    voice.offset += delta.mul_f32(voice.params.pitch.max(0.0));
+
+                    if voice.offset < voice.length {
+                        false
+                    } else if voice.params.is_looping && !voice.length.is_zero() {
+                        voice.offset = Duration::from_secs_f64(
+                            voice.offset.as_secs_f64() % voice.length.as_secs_f64(),
+                        );
+                        false
+                    } else {
+                        true
+                    }
+                }
+            };
+
+            if is_finished {
+                self.finish(index)?;
+            }
+        }
+
+        self.rebalance()
+    }
+
+    fn voice(&self, id: VoiceId) -> AllenResult<&Voice> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.voice.as_ref())
+            .ok_or(AllenError::InvalidName)
+    }
+
+    fn voice_mut(&mut self, id: VoiceId) -> AllenResult<&mut Voice> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.voice.as_mut())
+            .ok_or(AllenError::InvalidName)
+    }
+
+    fn finish(&mut self, index: usize) -> AllenResult<()> {
+        let slot = &mut self.slots[index];
+        let voice = slot.voice.take().expect("voice exists");
+        slot.generation = slot.generation.wrapping_add(1);
+
+        if let Some(source) = voice.source {
+            self.release_source(source)?;
+        }
+
+        Ok(())
+    }
+
+    fn release_source(&mut self, source: usize) -> AllenResult<()> {
+        self.owners[source] = None;
+
+        let source = &self.sources[source];
+        source.stop()?;
+        source.set(AL_BUFFER, 0)
+    }
+
+    /// Takes the source away from a voice, remembering where it was at.
+    fn virtualize(&mut self, index: usize) -> AllenResult<()> {
+        let voice = self.slots[index].voice.as_mut().expect("voice exists");
+
+        if let Some(source) = voice.source.take() {
+            voice.offset = Duration::from_secs_f32(self.sources[source].time_in_secs()?.max(0.0));
+            self.release_source(source)?;
+        }
+
+        Ok(())
+    }
+
+    /// Gives a free source to a voice, and starts playing it from where it's at.
+    fn realize(&mut self, index: usize, source: usize) -> AllenResult<()> {
+        let generation = self.slots[index].generation;
+        let voice = self.slots[index].voice.as_mut().expect("voice exists");
+
+        let al_source = &self.sources[source];
+        al_source.set(AL_BUFFER, voice.buffer.handle() as i32)?;
+        voice.params.apply(al_source)?;
+        al_source.set_time_in_secs(voice.offset.as_secs_f32())?;
+        al_source.play()?;
+
+        voice.source = Some(source);
+        self.owners[source] = Some(VoiceId {
+            index: index as u32,
+            generation,
+        });
+
+        Ok(())
+    }
+
+    /// Makes sure the most important voices are the ones holding sources.
+    fn rebalance(&mut self) -> AllenResult<()> {
+        let listener_position = self.context.listener().position()?;
+
+        let mut ranked = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let voice = slot.voice.as_ref()?;
+                (!voice.is_paused).then(|| {
+                    (
+                        index,
+                        voice.params.priority,
+                        voice.params.audibility(listener_position),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+
+        ranked.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then_with(|| b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal))
+        });
+
+        let winners = ranked
+            .iter()
+            .take(self.sources.len())
+            .map(|(index, ..)| *index)
+            .collect::<Vec<_>>();
+
+        // Free up sources first, so that they can be handed out below.
+        for index in 0..self.slots.len() {
+            let holds_source =
+                matches!(&self.slots[index].voice, Some(voice) if voice.source.is_some());
+
+            if holds_source && !winners.contains(&index) {
+                self.virtualize(index)?;
+            }
+        }
+
+        for index in winners {
+            let has_source =
+                matches!(&self.slots[index].voice, Some(voice) if voice.source.is_some());
+
+            if !has_source {
+                let source = self
+                    .owners
+                    .iter()
+                    .position(Option::is_none)
+                    .expect("there are as many winners as sources");
+
+                // Retrying on every update wouldn't fare any better.
+                if self.realize(index, source).is_err() {
+                    self.release_source(source)?;
+                    self.finish(index)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}